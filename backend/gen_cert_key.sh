@@ -0,0 +1,2 @@
+#!/bin/sh
+cat /dev/urandom | head -c32 > CERT_SIGNING_KEY
@@ -123,6 +123,19 @@ pub fn with_auth(role: Role) -> impl Filter<Extract = (String,), Error = Rejecti
         .and_then(authorize)
 }
 
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    token: String,
+}
+
+/// Like `with_auth`, but takes the token from the query parameter `token`,
+/// since browsers cannot set headers when opening a WebSocket.
+pub fn with_token_auth(role: Role) -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    warp::query::<TokenQuery>()
+        .map(move |query: TokenQuery| (role.clone(), query.token))
+        .and_then(authorize_token)
+}
+
 pub fn create_jwt(uid: &str, role: &Role) -> Result<String> {
     let expiration: i64 = Utc::now()
         .checked_add_signed(chrono::Duration::days(30))
@@ -140,24 +153,30 @@ pub fn create_jwt(uid: &str, role: &Role) -> Result<String> {
 
 async fn authorize((role, headers): (Role, HeaderMap<HeaderValue>)) -> WebResult<String> {
     match jwt_from_header(&headers) {
-        Ok(jwt) => {
-            log::info!("JWT = {}", &jwt);
-            // TODO: check if token has expired
-            let decoded = decode::<Claims>(
-                &jwt,
-                &DecodingKey::from_secret(&JWT_KEY.token),
-                &Validation::new(Algorithm::HS512),
-            )
-            .map_err(|_| reject::custom(Error::JWTTokenError))?;
-            if role == Role::Admin && Role::from_str(&decoded.claims.role) != Role::Admin {
-                return Err(reject::custom(Error::NoPermissionError));
-            }
-            Ok(decoded.claims.sub)
-        }
+        Ok(jwt) => verify_jwt(&jwt, &role),
         Err(e) => return Err(reject::custom(e)),
     }
 }
 
+async fn authorize_token((role, jwt): (Role, String)) -> WebResult<String> {
+    verify_jwt(&jwt, &role)
+}
+
+fn verify_jwt(jwt: &String, role: &Role) -> WebResult<String> {
+    log::info!("JWT = {}", &jwt);
+    // TODO: check if token has expired
+    let decoded = decode::<Claims>(
+        &jwt,
+        &DecodingKey::from_secret(&JWT_KEY.token),
+        &Validation::new(Algorithm::HS512),
+    )
+    .map_err(|_| reject::custom(Error::JWTTokenError))?;
+    if Role::from_str(&decoded.claims.role).lt(role) {
+        return Err(reject::custom(Error::NoPermissionError));
+    }
+    Ok(decoded.claims.sub)
+}
+
 fn jwt_from_header(headers: &HeaderMap<HeaderValue>) -> Result<String> {
     let header: &warp::http::HeaderValue = match headers.get(AUTHORIZATION) {
         Some(v) => v,
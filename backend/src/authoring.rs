@@ -0,0 +1,227 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::db::{AuditAction, AuditEntry, Direction, DoorType, Game, Riddle, Room, DB};
+use crate::{error::Error, Result};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use log;
+use serde::Serialize;
+
+fn invalid(message: String) -> Result<()> {
+    Err(Error::ContentValidationError(message))
+}
+
+pub fn validate_game(game: &Game) -> Result<()> {
+    if game.name.trim().is_empty() {
+        return invalid("game must have a name".to_string());
+    }
+    Ok(())
+}
+
+pub fn validate_riddle(riddle: &Riddle) -> Result<()> {
+    if riddle.solution.is_empty() && riddle.script.is_none() {
+        return invalid("riddle needs a solution or a script".to_string());
+    }
+    if let Some(deduction) = riddle.deduction {
+        if deduction < 0 {
+            return invalid("deduction must not be negative".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Checks a single door of `room`. The riddle behind the door, if any, must
+/// exist, have the same level as the door, and may guard at most one other
+/// door side, namely the one in the room on the other side. Doors that do
+/// not lead to their counterpart must name an existing target room of the
+/// same game.
+pub async fn validate_direction(db: &DB, room: &Room, direction: &Direction) -> Result<()> {
+    if direction.direction.trim().is_empty() {
+        return invalid("door must have a name".to_string());
+    }
+    match (&direction.door, &direction.target, &direction.riddle_id) {
+        (_, Some(target), _) => match db.get_room(target).await {
+            Ok(other) => {
+                if other.game_id != room.game_id {
+                    return invalid(format!(
+                        "door '{}' leads to a room of another game",
+                        direction.direction
+                    ));
+                }
+            }
+            Err(Error::RoomNotFoundError) => {
+                return invalid(format!(
+                    "door '{}' leads to a room that does not exist",
+                    direction.direction
+                ))
+            }
+            Err(e) => return Err(e),
+        },
+        (DoorType::Regular, None, Some(_)) => {
+            if direction.counterpart().is_none() {
+                return invalid(format!(
+                    "door '{}' must declare its counterpart",
+                    direction.direction
+                ));
+            }
+        }
+        _ => return invalid(format!("door '{}' needs a target", direction.direction)),
+    }
+    if let Some(item) = &direction.item {
+        if item.trim().is_empty() {
+            return invalid(format!(
+                "door '{}' requires an item without a name",
+                direction.direction
+            ));
+        }
+    }
+    if let Some(condition) = &direction.condition {
+        for riddle_id in condition.riddles.iter() {
+            match db.get_riddle_by_oid(riddle_id).await {
+                Ok(Some(_)) => (),
+                Ok(None) => {
+                    return invalid(format!(
+                        "door '{}' requires a riddle that does not exist",
+                        direction.direction
+                    ))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    let riddle_id: ObjectId = match direction.riddle_id {
+        Some(riddle_id) => riddle_id,
+        None => return Ok(()),
+    };
+    let riddle: Riddle = match db.get_riddle_by_oid(&riddle_id).await {
+        Ok(Some(riddle)) => riddle,
+        Ok(None) => {
+            return invalid(format!(
+                "door '{}' references a riddle that does not exist",
+                direction.direction
+            ))
+        }
+        Err(e) => return Err(e),
+    };
+    if riddle.level != direction.level {
+        return invalid(format!(
+            "door '{}' has level {}, but its riddle has level {}",
+            direction.direction, direction.level, riddle.level
+        ));
+    }
+    let rooms: Vec<Room> = match db.get_rooms_by_riddle(&riddle.id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(e),
+    };
+    let other_sides: usize = rooms
+        .iter()
+        .filter(|other| other.id != room.id)
+        .flat_map(|other| other.neighbors.iter())
+        .filter(|neighbor| neighbor.riddle_id == Some(riddle.id))
+        .count();
+    if other_sides > 1 {
+        return invalid(format!(
+            "riddle of door '{}' already guards another door",
+            direction.direction
+        ));
+    }
+    Ok(())
+}
+
+pub async fn validate_room(db: &DB, room: &Room) -> Result<()> {
+    match db.get_game(&room.game_id).await {
+        Ok(_) => (),
+        Err(e) => return Err(e),
+    }
+    for (idx, direction) in room.neighbors.iter().enumerate() {
+        if room.neighbors[..idx]
+            .iter()
+            .any(|other| other.direction == direction.direction)
+        {
+            return invalid(format!("duplicate door '{}'", direction.direction));
+        }
+        if direction.riddle_id.is_some()
+            && room.neighbors[..idx]
+                .iter()
+                .any(|other| other.riddle_id == direction.riddle_id)
+        {
+            return invalid(format!(
+                "door '{}' shares its riddle with another door of the same room",
+                direction.direction
+            ));
+        }
+        match validate_direction(db, room, direction).await {
+            Ok(()) => (),
+            Err(e) => return Err(e),
+        }
+    }
+    for (idx, object) in room.objects.iter().enumerate() {
+        if object.name.trim().is_empty() {
+            return invalid("object must have a name".to_string());
+        }
+        if room.objects[..idx]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(&object.name))
+        {
+            return invalid(format!("duplicate object '{}'", object.name));
+        }
+    }
+    for (idx, item) in room.items.iter().enumerate() {
+        if item.name.trim().is_empty() {
+            return invalid("item must have a name".to_string());
+        }
+        if room.items[..idx]
+            .iter()
+            .any(|other| other.is_named(&item.name))
+        {
+            return invalid(format!("duplicate item '{}'", item.name));
+        }
+    }
+    let rooms: Vec<Room> = match db.get_rooms_of_game(&room.game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(e),
+    };
+    if rooms
+        .iter()
+        .any(|other| other.id != room.id && other.number == room.number)
+    {
+        return invalid(format!("room number {} is already taken", room.number));
+    }
+    Ok(())
+}
+
+/// Creates an entry for the audit trail. `item` is the document as written
+/// to `collection`, or `None` if it has been deleted. The entry is written
+/// together with the change, see `DB::insert_audited`.
+pub fn audit_entry<T: Serialize>(
+    username: &String,
+    action: AuditAction,
+    collection: &String,
+    object_id: &ObjectId,
+    version: u32,
+    item: Option<&T>,
+) -> Result<AuditEntry> {
+    log::info!(
+        "audit_entry(); username = {}, action = {:?}, collection = {}, object_id = {}",
+        username,
+        action,
+        collection,
+        object_id
+    );
+    let data: Option<bson::Bson> = match item.map(bson::to_bson).transpose() {
+        Ok(data) => data,
+        Err(e) => return Err(Error::DatabaseQueryError(e.to_string())),
+    };
+    Ok(AuditEntry {
+        id: ObjectId::new(),
+        username: username.clone(),
+        action,
+        collection: collection.clone(),
+        object_id: *object_id,
+        version,
+        timestamp: Utc::now().timestamp(),
+        data,
+    })
+}
@@ -0,0 +1,368 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+// Runs riddle scripts outside the server, in the same sandbox, so that
+// designers can try them out and test them before uploading them. The
+// modules are shared with the server, which uses more of them.
+#[allow(dead_code)]
+#[path = "../error.rs"]
+mod error;
+#[allow(dead_code)]
+#[path = "../i18n.rs"]
+mod i18n;
+#[allow(dead_code)]
+#[path = "../script_lib.rs"]
+mod script_lib;
+#[allow(dead_code)]
+#[path = "../scripting.rs"]
+mod scripting;
+#[allow(dead_code)]
+#[path = "../wasm.rs"]
+mod wasm;
+
+use error::Error;
+use script_lib::ScriptPlayer;
+use scripting::{Attachment, ScriptEngine, ScriptEnv, ScriptResult};
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+type Result<T> = std::result::Result<T, error::Error>;
+
+const USAGE: &str = "Usage: labyrinth-script [run <script> [--seed <n>] [--user <name>] [--level <n>] [--locale <tag>] [--save <dir>] [--ignore-case] [<guess> ...] | test <script> <cases> | check <script>]";
+
+/// A test suite: the cases are run one after another, each in a fresh
+/// script environment.
+#[derive(Deserialize, Debug)]
+struct Suite {
+    #[serde(default)]
+    ignore_case: bool,
+    cases: Vec<Case>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Case {
+    seed: i64,
+    #[serde(default = "default_username")]
+    username: String,
+    #[serde(default)]
+    level: u32,
+    #[serde(default)]
+    locale: Option<String>,
+    /// The expected solution.
+    #[serde(default)]
+    solution: Option<String>,
+    /// Text the task is expected to contain.
+    #[serde(default)]
+    task_contains: Option<String>,
+    #[serde(default)]
+    guesses: Vec<GuessCase>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GuessCase {
+    guess: String,
+    correct: bool,
+    /// The expected feedback of `verify()` or `try()`.
+    #[serde(default)]
+    feedback: Option<String>,
+}
+
+fn default_username() -> String {
+    "player".to_string()
+}
+
+fn read_file(path: &str) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) => Err(Error::GameFileError(format!("{}: {}", path, e))),
+    }
+}
+
+/// Reads a Lua script, or a WebAssembly module if `path` ends in `.wasm`,
+/// which is base64-encoded the way the server stores it.
+fn read_script(path: &str) -> Result<(ScriptEngine, String)> {
+    let is_wasm: bool = match Path::new(path).extension() {
+        Some(ext) => ext.eq_ignore_ascii_case("wasm"),
+        None => false,
+    };
+    match is_wasm {
+        true => match fs::read(path) {
+            Ok(wasm) => Ok((ScriptEngine::Wasm, base64::encode(wasm))),
+            Err(e) => Err(Error::GameFileError(format!("{}: {}", path, e))),
+        },
+        false => Ok((ScriptEngine::Lua, read_file(path)?)),
+    }
+}
+
+fn normalize(text: &str, ignore_case: bool) -> String {
+    match ignore_case {
+        true => text.to_lowercase(),
+        false => text.to_string(),
+    }
+}
+
+fn is_correct(result: &ScriptResult, guess: &str, ignore_case: bool) -> bool {
+    match (result.verdict, &result.solution) {
+        (Some(correct), _) => correct,
+        (None, Some(solution)) => normalize(solution, ignore_case) == normalize(guess, ignore_case),
+        (None, None) => false,
+    }
+}
+
+fn describe_data(data: &[u8]) -> String {
+    match std::str::from_utf8(data) {
+        Ok(text) => text.to_string(),
+        Err(_) => format!("<{} bytes of binary data>", data.len()),
+    }
+}
+
+/// Writes `data` to the file `name` in `dir`. Only the last component of
+/// `name` is used, as the script chooses it.
+fn save(dir: &str, name: &str, data: &[u8]) -> Result<()> {
+    let file_name = match Path::new(name).file_name() {
+        Some(file_name) => file_name,
+        None => {
+            return Err(Error::GameFileError(format!(
+                "invalid file name {:?}",
+                name
+            )))
+        }
+    };
+    let path = Path::new(dir).join(file_name);
+    match fs::write(&path, data) {
+        Ok(()) => {
+            println!("saved {}", path.display());
+            Ok(())
+        }
+        Err(e) => Err(Error::GameFileError(format!("{}: {}", path.display(), e))),
+    }
+}
+
+/// Runs the script with the given seed and prints what the player would
+/// get to see, then tries the guesses one after another.
+fn run(path: &str, options: &[String]) -> Result<()> {
+    let (engine, script) = read_script(path)?;
+    let mut seed: i64 = 0;
+    let mut username: String = default_username();
+    let mut level: u32 = 0;
+    let mut locale: Option<String> = Option::default();
+    let mut save_dir: Option<String> = Option::default();
+    let mut ignore_case: bool = false;
+    let mut guesses: Vec<String> = Vec::new();
+    let mut args = options.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" | "--user" | "--level" | "--locale" | "--save" => {
+                let value: &String = match args.next() {
+                    Some(value) => value,
+                    None => return Err(Error::UnknownCommandError),
+                };
+                match arg.as_str() {
+                    "--seed" => match value.parse() {
+                        Ok(value) => seed = value,
+                        Err(_) => return Err(Error::UnknownCommandError),
+                    },
+                    "--level" => match value.parse() {
+                        Ok(value) => level = value,
+                        Err(_) => return Err(Error::UnknownCommandError),
+                    },
+                    "--user" => username = value.clone(),
+                    "--locale" => locale = Some(value.clone()),
+                    _ => save_dir = Some(value.clone()),
+                }
+            }
+            "--ignore-case" => ignore_case = true,
+            _ => guesses.push(arg.clone()),
+        }
+    }
+    let player = ScriptPlayer {
+        username,
+        level,
+        locale,
+    };
+    let mut env: ScriptEnv = ScriptEnv::new(engine, seed, &player);
+    let result: ScriptResult = env.evaluate(&script, Option::default(), scripting::deadline())?;
+    println!("seed:      {}", seed);
+    println!("name:      {}", result.name.clone().unwrap_or_default());
+    println!(
+        "mime type: {}",
+        result.mime_type.clone().unwrap_or_default()
+    );
+    println!("solution:  {}", result.solution.clone().unwrap_or_default());
+    if !result.items.is_empty() {
+        println!("items:     {}", result.items.join(", "));
+    }
+    let task: Vec<u8> = result.task.clone().unwrap_or_default();
+    println!("task:\n{}", describe_data(&task));
+    for attachment in result.attachments.iter() {
+        println!(
+            "attachment: {} ({}, {} bytes)",
+            attachment.name,
+            attachment.mime_type,
+            attachment.data.len()
+        );
+    }
+    if let Some(dir) = &save_dir {
+        save(
+            dir,
+            &result.name.clone().unwrap_or("task".to_string()),
+            &task,
+        )?;
+        for attachment in result.attachments.iter() {
+            let Attachment { name, data, .. } = attachment;
+            save(dir, name, data)?;
+        }
+    }
+    for guess in guesses.into_iter() {
+        let result: ScriptResult =
+            env.evaluate(&script, Some(guess.clone()), scripting::deadline())?;
+        println!(
+            "guess {:?}: {}{}",
+            &guess,
+            match is_correct(&result, &guess, ignore_case) {
+                true => "correct",
+                false => "wrong",
+            },
+            match result.feedback {
+                Some(feedback) => format!(" ({})", feedback),
+                None => String::new(),
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Runs a single case and returns what did not turn out as expected.
+fn run_case(engine: ScriptEngine, script: &str, case: &Case, ignore_case: bool) -> Vec<String> {
+    let player = ScriptPlayer {
+        username: case.username.clone(),
+        level: case.level,
+        locale: case.locale.clone(),
+    };
+    let mut env: ScriptEnv = ScriptEnv::new(engine, case.seed, &player);
+    let mut failures: Vec<String> = Vec::new();
+    let result: ScriptResult = match env.evaluate(script, Option::default(), scripting::deadline())
+    {
+        Ok(result) => result,
+        Err(e) => return vec![e.to_string()],
+    };
+    if let Some(expected) = &case.solution {
+        if result.solution.as_ref() != Some(expected) {
+            failures.push(format!(
+                "expected solution {:?}, got {:?}",
+                expected,
+                result.solution.clone().unwrap_or_default()
+            ));
+        }
+    }
+    if let Some(expected) = &case.task_contains {
+        let task: String =
+            String::from_utf8_lossy(&result.task.clone().unwrap_or_default()).into_owned();
+        if !task.contains(expected.as_str()) {
+            failures.push(format!("task does not contain {:?}", expected));
+        }
+    }
+    for guess in case.guesses.iter() {
+        let result: ScriptResult =
+            match env.evaluate(script, Some(guess.guess.clone()), scripting::deadline()) {
+                Ok(result) => result,
+                Err(e) => {
+                    failures.push(format!("guess {:?}: {}", &guess.guess, e));
+                    continue;
+                }
+            };
+        if is_correct(&result, &guess.guess, ignore_case) != guess.correct {
+            failures.push(format!(
+                "guess {:?} should be {}",
+                &guess.guess,
+                match guess.correct {
+                    true => "correct",
+                    false => "wrong",
+                }
+            ));
+        }
+        if let Some(expected) = &guess.feedback {
+            if result.feedback.as_ref() != Some(expected) {
+                failures.push(format!(
+                    "guess {:?}: expected feedback {:?}, got {:?}",
+                    &guess.guess, expected, result.feedback
+                ));
+            }
+        }
+    }
+    failures
+}
+
+/// Runs all cases of the YAML or JSON file at `cases_path` against the
+/// script. Returns whether all cases passed.
+fn test(path: &str, cases_path: &str) -> Result<bool> {
+    let (engine, script) = read_script(path)?;
+    let contents: String = read_file(cases_path)?;
+    let suite: Suite = match serde_yaml::from_str(&contents) {
+        Ok(suite) => suite,
+        Err(e) => return Err(Error::GameFileError(format!("{}: {}", cases_path, e))),
+    };
+    let mut passed: usize = 0;
+    for (n, case) in suite.cases.iter().enumerate() {
+        let failures: Vec<String> = run_case(engine, &script, case, suite.ignore_case);
+        match failures.is_empty() {
+            true => {
+                println!("case {} (seed {}): ok", n + 1, case.seed);
+                passed += 1;
+            }
+            false => {
+                println!("case {} (seed {}): FAILED", n + 1, case.seed);
+                for failure in failures.iter() {
+                    println!("    {}", failure);
+                }
+            }
+        }
+    }
+    println!("{} of {} cases passed", passed, suite.cases.len());
+    Ok(passed == suite.cases.len())
+}
+
+/// Validates the script the way the server does before it stores it.
+fn check(path: &str) -> Result<bool> {
+    let (engine, script) = read_script(path)?;
+    match scripting::check_script(engine, &script) {
+        Ok(()) => {
+            println!("{}: ok", path);
+            Ok(true)
+        }
+        Err(Error::ScriptValidationError(message)) => {
+            for problem in message.split("; ") {
+                println!("{}: {}", path, problem);
+            }
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let outcome: Result<bool> = match args.as_slice() {
+        [command, path, options @ ..] if command == "run" => run(path, options).map(|()| true),
+        [command, path, cases] if command == "test" => test(path, cases),
+        [command, path] if command == "check" => check(path),
+        _ => Err(Error::UnknownCommandError),
+    };
+    match outcome {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(Error::UnknownCommandError) => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,125 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::{error::Error, Result};
+use bson::oid::ObjectId;
+use chrono::{TimeZone, Utc};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+use lazy_static::lazy_static;
+use log;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+pub struct CertificateSigningKey {
+    pub keypair: Keypair,
+}
+
+impl CertificateSigningKey {
+    pub fn new_from_file(path: &str) -> CertificateSigningKey {
+        log::info!("Reading CERT_SIGNING_KEY ...");
+        let bytes: Vec<u8> = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                panic!("{}", e);
+            }
+        };
+        let secret: SecretKey = match SecretKey::from_bytes(&bytes) {
+            Ok(secret) => secret,
+            Err(e) => {
+                panic!("{}", e);
+            }
+        };
+        let public: PublicKey = PublicKey::from(&secret);
+        CertificateSigningKey {
+            keypair: Keypair { secret, public },
+        }
+    }
+}
+
+lazy_static! {
+    static ref CERT_KEY: CertificateSigningKey =
+        CertificateSigningKey::new_from_file("CERT_SIGNING_KEY");
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Certificate {
+    pub username: String,
+    pub game_id: ObjectId,
+    pub game: String,
+    pub finished: i64,
+    pub score: i32,
+    pub rank: u32,
+    pub issued: i64,
+}
+
+impl Certificate {
+    /// The bytes covered by the signature. The field order of the struct is
+    /// fixed, so serializing the certificate again always yields the same bytes.
+    fn message(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|_| Error::CertificateSerializationError)
+    }
+
+    pub fn sign(&self) -> Result<String> {
+        let message: Vec<u8> = match self.message() {
+            Ok(message) => message,
+            Err(e) => return Err(e),
+        };
+        let signature: Signature = CERT_KEY.keypair.sign(&message);
+        Ok(base64::encode(signature.to_bytes()))
+    }
+
+    pub fn verify(&self, signature: &String) -> Result<bool> {
+        let message: Vec<u8> = match self.message() {
+            Ok(message) => message,
+            Err(e) => return Err(e),
+        };
+        let signature: Vec<u8> = match base64::decode(signature) {
+            Ok(signature) => signature,
+            Err(_) => return Ok(false),
+        };
+        let signature: Signature = match Signature::try_from(signature.as_slice()) {
+            Ok(signature) => signature,
+            Err(_) => return Ok(false),
+        };
+        Ok(CERT_KEY.keypair.public.verify(&message, &signature).is_ok())
+    }
+
+    pub fn render_svg(&self, signature: &String) -> String {
+        let finished = Utc.timestamp(self.finished, 0);
+        format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="800" height="560" viewBox="0 0 800 560">
+  <rect x="0" y="0" width="800" height="560" fill="#111"/>
+  <rect x="20" y="20" width="760" height="520" fill="none" stroke="#3f3" stroke-width="4"/>
+  <g font-family="monospace" fill="#3f3" text-anchor="middle">
+    <text x="400" y="110" font-size="40">CERTIFICATE OF ESCAPE</text>
+    <text x="400" y="180" font-size="20">This is to certify that</text>
+    <text x="400" y="240" font-size="36">{}</text>
+    <text x="400" y="300" font-size="20">found the way out of</text>
+    <text x="400" y="350" font-size="28">{}</text>
+    <text x="400" y="410" font-size="18">on {} with a score of {} (rank {})</text>
+    <text x="400" y="500" font-size="9">{}</text>
+  </g>
+</svg>
+"##,
+            escape_xml(&self.username),
+            escape_xml(&self.game),
+            finished.format("%Y-%m-%d %H:%M UTC"),
+            self.score,
+            self.rank,
+            escape_xml(signature),
+        )
+    }
+}
+
+pub fn public_key() -> String {
+    base64::encode(CERT_KEY.keypair.public.to_bytes())
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -2,15 +2,20 @@
  * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
  * All rights reserved.
  */
-use crate::{auth::Role, b64, error::Error::*, passwd::Password, Result};
+use crate::i18n::LocalizedText;
+use crate::scripting::ScriptEngine;
+use crate::{auth::Role, b64, error::Error::*, passwd::Password, Result, OPPOSITE};
 use bson::oid::ObjectId;
 use chrono::{serde::ts_seconds_option, DateTime, Utc};
 use futures::stream::{StreamExt, TryStreamExt};
 use log;
 use mongodb::bson::doc;
-use mongodb::options::{ClientOptions, FindOneOptions, FindOptions, UpdateOptions};
-use mongodb::results::UpdateResult;
-use mongodb::{Client, Collection, Database};
+use mongodb::options::{
+    ClientOptions, FindOneAndUpdateOptions, FindOneOptions, FindOptions, ReplaceOptions,
+    ReturnDocument, UpdateOptions,
+};
+use mongodb::results::{DeleteResult, UpdateResult};
+use mongodb::{Client, ClientSession, Collection, Database};
 use rand::{distributions::Distribution, Rng};
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
@@ -49,8 +54,11 @@ pub struct UploadedFile {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Riddle {
     #[serde(rename = "_id")]
+    #[serde(default)]
     pub id: ObjectId,
     #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
     pub difficulty: i32,
     #[serde(default)]
     pub deduction: Option<i32>,
@@ -69,29 +77,224 @@ pub struct Riddle {
     pub credits: Option<String>,
     #[serde(default)]
     pub script: Option<String>,
+    /// The engine `script` is written for, Lua if not set.
+    #[serde(default)]
+    pub script_engine: Option<ScriptEngine>,
     #[serde(default)]
     pub external_password_input: bool,
 }
 
+impl Riddle {
+    /// Brings a guess into the form it is compared in with the solution.
+    pub fn normalize(&self, text: &str) -> String {
+        match self.ignore_case.unwrap_or(false) {
+            true => text.to_lowercase(),
+            false => text.to_string(),
+        }
+    }
+
+    /// Tells whether `text` contains the (static) solution of the riddle.
+    pub fn is_revealed_by(&self, text: &str) -> bool {
+        !self.solution.is_empty()
+            && self
+                .normalize(text)
+                .contains(&self.normalize(&self.solution))
+    }
+}
+
+/// A player's rating of a riddle they have solved. Each player rates a
+/// riddle at most once; rating again replaces the earlier rating.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RiddleFeedback {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub riddle_id: ObjectId,
+    pub username: String,
+    /// 1 (very easy) to 5 (very hard)
+    pub difficulty: u8,
+    /// 1 (boring) to 5 (great fun)
+    pub fun: u8,
+    #[serde(default)]
+    pub comment: Option<String>,
+    pub timestamp: i64,
+}
+
+/// The average ratings of a riddle.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RiddleRating {
+    #[serde(rename = "_id")]
+    pub riddle_id: ObjectId,
+    pub count: u32,
+    pub difficulty: f64,
+    pub fun: f64,
+    /// The riddle's `difficulty` as used for scoring.
+    #[serde(default)]
+    pub score: Option<i32>,
+}
+
+/// A note a player has left in a room for others to read.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Note {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub room_id: ObjectId,
+    pub username: String,
+    pub text: String,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub hidden: bool,
+    /// Users who have reported the note.
+    #[serde(default)]
+    pub reports: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum DoorType {
+    /// Leads to the room with the counterpart door guarded by the same
+    /// riddle, or to `target` if given.
+    Regular,
+    /// Leads to `target`; there is no way back through this door.
+    OneWay,
+    /// Leads to `target`, which may be anywhere in the game.
+    Teleporter,
+}
+
+impl Default for DoorType {
+    fn default() -> Self {
+        DoorType::Regular
+    }
+}
+
+/// Additional requirements a door has on top of its riddle.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct DoorCondition {
+    /// All of these riddles must have been solved.
+    #[serde(default)]
+    pub riddles: Vec<ObjectId>,
+    /// The user must have reached at least this level.
+    #[serde(default)]
+    pub level: Option<u32>,
+}
+
+impl DoorCondition {
+    pub fn is_met_by(&self, user: &User) -> bool {
+        let riddles_solved: bool = self
+            .riddles
+            .iter()
+            .all(|riddle_id| user.solved.iter().any(|s| &s.riddle_id == riddle_id));
+        let level_reached: bool = match self.level {
+            Some(level) => user.level >= level,
+            None => true,
+        };
+        riddles_solved && level_reached
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Direction {
     pub direction: String,
-    pub riddle_id: ObjectId,
+    /// The riddle that opens the door. Doors without a riddle are always open.
+    #[serde(default)]
+    pub riddle_id: Option<ObjectId>,
     pub level: u32,
+    /// Name of the door on the other side. Only needed if it is not the
+    /// opposite of `direction`, e.g. for named exits like "portal".
+    #[serde(default)]
+    pub counterpart: Option<String>,
+    #[serde(default)]
+    pub door: DoorType,
+    /// The room the door leads to, if it is not found via the counterpart.
+    #[serde(default)]
+    pub target: Option<ObjectId>,
+    #[serde(default)]
+    pub condition: Option<DoorCondition>,
+    /// Name of an item the player must carry to pass.
+    #[serde(default)]
+    pub item: Option<String>,
+}
+
+impl Direction {
+    /// Returns the name of the door on the other side, which is either
+    /// declared explicitly or looked up in `OPPOSITE`.
+    pub fn counterpart(&self) -> Option<String> {
+        match &self.counterpart {
+            Some(counterpart) => Some(counterpart.clone()),
+            None => OPPOSITE.get(&self.direction).cloned(),
+        }
+    }
+
+    /// Tells whether `user` may pass: the riddle, if any, is solved, the
+    /// condition, if any, is met, and the required item, if any, is carried.
+    pub fn is_open_for(&self, user: &User) -> bool {
+        self.riddle_id.map_or(true, |riddle_id| {
+            user.solved.iter().any(|s| s.riddle_id == riddle_id)
+        }) && self
+            .condition
+            .as_ref()
+            .map_or(true, |condition| condition.is_met_by(user))
+            && self.item.as_ref().map_or(true, |item| {
+                user.inventory.iter().any(|carried| carried.is_named(item))
+            })
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Game {
     #[serde(rename = "_id")]
+    #[serde(default)]
     pub id: ObjectId,
+    #[serde(default)]
+    pub version: u32,
+    pub name: String,
+}
+
+/// Something in a room the player can take a closer look at.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RoomObject {
+    pub name: String,
+    #[serde(default)]
+    pub description: LocalizedText,
+    /// Replaces `description` once all riddles of the room are solved.
+    #[serde(default)]
+    pub solved_description: Option<LocalizedText>,
+}
+
+/// Something the player can carry around.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Item {
     pub name: String,
+    #[serde(default)]
+    pub description: LocalizedText,
+}
+
+impl Item {
+    pub fn new(name: &String) -> Item {
+        Item {
+            name: name.clone(),
+            description: LocalizedText::new(),
+        }
+    }
+
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+}
+
+/// An item together with the room it was taken from or dropped in.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PlacedItem {
+    pub item: Item,
+    pub room_id: ObjectId,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Room {
     #[serde(rename = "_id")]
+    #[serde(default)]
     pub id: ObjectId,
     #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
     pub number: u32,
     #[serde(default)]
     pub coords: Option<String>,
@@ -102,6 +305,54 @@ pub struct Room {
     pub entry: Option<bool>,
     #[serde(default)]
     pub exit: Option<bool>,
+    #[serde(default)]
+    pub title: LocalizedText,
+    #[serde(default)]
+    pub description: LocalizedText,
+    /// Replaces `description` once all riddles of the room are solved.
+    #[serde(default)]
+    pub solved_description: Option<LocalizedText>,
+    #[serde(default)]
+    pub ascii_art: Option<String>,
+    #[serde(default)]
+    pub objects: Vec<RoomObject>,
+    /// Items lying around in the room when the game starts.
+    #[serde(default)]
+    pub items: Vec<Item>,
+}
+
+impl Room {
+    /// Returns true if `user` has solved all riddles guarding the doors of this room.
+    pub fn is_solved_by(&self, user: &User) -> bool {
+        self.neighbors
+            .iter()
+            .filter_map(|neighbor| neighbor.riddle_id)
+            .all(|riddle_id| user.solved.iter().any(|s| s.riddle_id == riddle_id))
+    }
+
+    /// Returns the items `user` sees in this room: the ones placed here that
+    /// they have not taken yet, and the ones they dropped here. Every player
+    /// has their own copy of the items.
+    pub fn items_visible_to(&self, user: &User) -> Vec<Item> {
+        let mut items: Vec<Item> = self
+            .items
+            .iter()
+            .filter(|item| {
+                !user
+                    .items_taken
+                    .iter()
+                    .any(|taken| taken.room_id == self.id && &taken.item == *item)
+            })
+            .cloned()
+            .collect();
+        items.extend(
+            user.items_dropped
+                .iter()
+                .filter(|dropped| dropped.room_id == self.id)
+                .map(|dropped| dropped.item.clone()),
+        );
+        items
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -157,6 +408,35 @@ impl WebauthnManagementData {
     }
 }
 
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct FinishedGame {
+    pub game_id: ObjectId,
+    pub timestamp: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// One change made through the authoring API. `data` holds the document as
+/// it was written; it is empty for deletions.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct AuditEntry {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub username: String,
+    pub action: AuditAction,
+    pub collection: String,
+    pub object_id: ObjectId,
+    pub version: u32,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub data: Option<bson::Bson>,
+}
+
 #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 pub struct RiddleAttempt {
     pub riddle_id: ObjectId,
@@ -165,6 +445,50 @@ pub struct RiddleAttempt {
     pub t0: Option<DateTime<Utc>>,
     #[serde(default)]
     pub dt: Option<i64>,
+    /// The seed the riddle's script was run with for this attempt.
+    #[serde(default)]
+    pub seed: Option<i64>,
+}
+
+/// The seed a riddle's script is run with for a player. It is drawn when
+/// the player opens the riddle for the first time and kept from then on.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RiddleSeed {
+    pub riddle_id: ObjectId,
+    pub seed: i64,
+}
+
+/// Players who share their progress: a riddle solved by one member counts
+/// for all of them. Members move through the labyrinth independently.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Team {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub invitations: Vec<String>,
+    #[serde(default)]
+    pub solved: Vec<RiddleAttempt>,
+    #[serde(default)]
+    pub level: u32,
+    #[serde(default)]
+    pub score: i32,
+}
+
+impl Team {
+    pub fn new(name: &String, founder: &String) -> Team {
+        Team {
+            id: ObjectId::new(),
+            name: name.clone(),
+            members: vec![founder.clone()],
+            invitations: Vec::new(),
+            solved: Vec::new(),
+            level: 0,
+            score: 0,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -190,13 +514,25 @@ pub struct User {
     pub solved: Vec<RiddleAttempt>,
     pub current_riddle_attempt: Option<RiddleAttempt>,
     #[serde(default)]
+    pub riddle_seeds: Vec<RiddleSeed>,
+    #[serde(default)]
     pub rooms_entered: Vec<ObjectId>,
     #[serde(default)]
+    pub finished: Vec<FinishedGame>,
+    #[serde(default)]
+    pub inventory: Vec<Item>,
+    #[serde(default)]
+    pub items_taken: Vec<PlacedItem>,
+    #[serde(default)]
+    pub items_dropped: Vec<PlacedItem>,
+    #[serde(default)]
     pub level: u32,
     #[serde(default)]
     pub score: i32,
     pub in_room: Option<ObjectId>,
     #[serde(default)]
+    pub team_id: Option<ObjectId>,
+    #[serde(default)]
     pub awaiting_second_factor: bool,
     #[serde(default)]
     #[serde(with = "b64")]
@@ -224,6 +560,14 @@ pub struct UserFullScoreData {
     pub in_room: Option<ObjectId>,
 }
 
+/// The riddles a user has solved, without anything else about the user.
+#[derive(Deserialize, Debug)]
+pub struct UserSolvedData {
+    pub username: String,
+    #[serde(default)]
+    pub solved: Vec<RiddleAttempt>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct UserCompactScoreData {
     pub username: String,
@@ -257,76 +601,1094 @@ impl User {
             last_login: Option::default(),
             solved: Vec::new(),
             current_riddle_attempt: Option::default(),
+            riddle_seeds: Vec::new(),
             rooms_entered: Vec::new(),
+            finished: Vec::new(),
+            inventory: Vec::new(),
+            items_taken: Vec::new(),
+            items_dropped: Vec::new(),
             level: 0,
             score: 0,
             in_room: Option::default(),
+            team_id: Option::default(),
             awaiting_second_factor: false,
             totp_key: totp_key,
             recovery_keys: Vec::new(),
             webauthn: WebauthnManagementData::new(),
         }
     }
-}
 
-pub struct KeyChars;
+    /// The seed the script of the riddle `riddle_id` is run with for this
+    /// user. Attempts started before seeds were kept per riddle only have
+    /// the seed of the current attempt.
+    pub fn seed_for(&self, riddle_id: &ObjectId) -> Option<i64> {
+        match self
+            .riddle_seeds
+            .iter()
+            .find(|seed| seed.riddle_id == *riddle_id)
+        {
+            Some(seed) => Some(seed.seed),
+            None => self
+                .current_riddle_attempt
+                .as_ref()
+                .filter(|attempt| attempt.riddle_id == *riddle_id)
+                .and_then(|attempt| attempt.seed),
+        }
+    }
+}
+
+/// Returns the IDs of all riddles guarding the doors of `rooms`, without duplicates.
+pub fn riddle_ids_of_rooms(rooms: &Vec<Room>) -> Vec<ObjectId> {
+    let mut riddle_ids: Vec<ObjectId> = Vec::new();
+    for room in rooms.iter() {
+        for neighbor in room.neighbors.iter() {
+            if let Some(riddle_id) = neighbor.riddle_id {
+                if !riddle_ids.contains(&riddle_id) {
+                    riddle_ids.push(riddle_id);
+                }
+            }
+        }
+    }
+    riddle_ids
+}
+
+pub struct KeyChars;
+
+impl Distribution<u8> for KeyChars {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u8 {
+        const RANGE: usize = 25 + 10;
+        const GEN_ASCII_STR_CHARSET: &[u8; RANGE] = b"abcdefghijkmnopqrstuvwxyz0123456789";
+        loop {
+            let idx: usize = rng.next_u32() as usize % RANGE;
+            return GEN_ASCII_STR_CHARSET[idx];
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DB {
+    pub client: Client,
+    pub name: String,
+    pub coll_games: String,
+    pub coll_rooms: String,
+    pub coll_riddles: String,
+    pub coll_users: String,
+    pub coll_audit: String,
+    pub coll_teams: String,
+    pub coll_notes: String,
+    pub coll_feedback: String,
+}
+
+/// Matches the document `oid` in version `version`. Documents created before
+/// versioning have no `version` field and count as version 0.
+fn versioned(oid: &ObjectId, version: u32) -> bson::Document {
+    match version {
+        0 => doc! {
+            "_id": oid,
+            "$or": [{ "version": 0 }, { "version": { "$exists": false } }],
+        },
+        _ => doc! { "_id": oid, "version": version },
+    }
+}
+
+impl DB {
+    pub async fn init() -> Result<Self> {
+        let url: String = env::var("DB_URL").expect("DB_URL is not in .env file");
+        let name: String = env::var("DB_NAME").expect("DB_NAME is not in .env file");
+        let coll_users: String =
+            env::var("DB_COLL_USERS").expect("DB_COLL_USERS is not in .env file");
+        let coll_riddles: String =
+            env::var("DB_COLL_RIDDLES").expect("DB_COLL_RIDDLES is not in .env file");
+        let coll_rooms: String =
+            env::var("DB_COLL_ROOMS").expect("DB_COLL_ROOMS is not in .env file");
+        let coll_games: String = env::var("DB_COLL_GAMES").unwrap_or_else(|_| "games".to_string());
+        let coll_audit: String = env::var("DB_COLL_AUDIT").unwrap_or_else(|_| "audit".to_string());
+        let coll_teams: String = env::var("DB_COLL_TEAMS").unwrap_or_else(|_| "teams".to_string());
+        let coll_notes: String = env::var("DB_COLL_NOTES").unwrap_or_else(|_| "notes".to_string());
+        let coll_feedback: String =
+            env::var("DB_COLL_FEEDBACK").unwrap_or_else(|_| "feedback".to_string());
+        let mut client_options: mongodb::options::ClientOptions =
+            ClientOptions::parse(url).await.unwrap();
+        client_options.app_name = Some(name.to_string());
+        Ok(Self {
+            client: Client::with_options(client_options).unwrap(),
+            name: name.to_string(),
+            coll_users: coll_users.to_string(),
+            coll_riddles: coll_riddles.to_string(),
+            coll_rooms: coll_rooms.to_string(),
+            coll_games: coll_games.to_string(),
+            coll_audit: coll_audit.to_string(),
+            coll_teams: coll_teams.to_string(),
+            coll_notes: coll_notes.to_string(),
+            coll_feedback: coll_feedback.to_string(),
+        })
+    }
+
+    pub fn get_database(&self) -> Database {
+        self.client.database(&self.name)
+    }
+
+    pub fn get_users_coll(&self) -> Collection<User> {
+        self.get_database().collection::<User>(&self.coll_users)
+    }
+
+    pub fn get_riddles_coll(&self) -> Collection<Riddle> {
+        self.get_database().collection::<Riddle>(&self.coll_riddles)
+    }
+
+    pub fn get_rooms_coll(&self) -> Collection<Room> {
+        self.get_database().collection::<Room>(&self.coll_rooms)
+    }
+
+    pub fn get_games_coll(&self) -> Collection<Game> {
+        self.get_database().collection::<Game>(&self.coll_games)
+    }
+
+    pub fn get_audit_coll(&self) -> Collection<AuditEntry> {
+        self.get_database()
+            .collection::<AuditEntry>(&self.coll_audit)
+    }
+
+    pub fn get_teams_coll(&self) -> Collection<Team> {
+        self.get_database().collection::<Team>(&self.coll_teams)
+    }
+
+    pub fn get_feedback_coll(&self) -> Collection<RiddleFeedback> {
+        self.get_database()
+            .collection::<RiddleFeedback>(&self.coll_feedback)
+    }
+
+    /// Stores `feedback`, replacing an earlier rating of the same riddle by
+    /// the same user. An earlier rating keeps its ID.
+    pub async fn set_riddle_feedback(&self, feedback: &RiddleFeedback) -> Result<()> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        match self
+            .get_feedback_coll()
+            .update_one(
+                doc! { "riddle_id": feedback.riddle_id, "username": &feedback.username },
+                doc! {
+                    "$set": {
+                        "difficulty": feedback.difficulty as i32,
+                        "fun": feedback.fun as i32,
+                        "comment": feedback.comment.clone(),
+                        "timestamp": feedback.timestamp,
+                    },
+                    "$setOnInsert": { "_id": feedback.id },
+                },
+                options,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    pub async fn get_riddle_feedback(&self, riddle_id: &ObjectId) -> Result<Vec<RiddleFeedback>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1 })
+            .build();
+        let cursor = match self
+            .get_feedback_coll()
+            .find(doc! { "riddle_id": riddle_id }, options)
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match cursor.try_collect().await {
+            Ok(feedback) => Ok(feedback),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Aggregates the ratings per riddle, optionally only for the riddle
+    /// with the ID `riddle_id`. The riddles rated hardest come first.
+    pub async fn get_riddle_ratings(
+        &self,
+        riddle_id: Option<&ObjectId>,
+    ) -> Result<Vec<RiddleRating>> {
+        let filter: bson::Document = match riddle_id {
+            Some(riddle_id) => doc! { "riddle_id": riddle_id },
+            None => doc! {},
+        };
+        let cursor = match self
+            .get_feedback_coll()
+            .aggregate(
+                vec![
+                    doc! { "$match": filter },
+                    doc! {
+                        "$group": {
+                            "_id": "$riddle_id",
+                            "count": { "$sum": 1 },
+                            "difficulty": { "$avg": "$difficulty" },
+                            "fun": { "$avg": "$fun" },
+                        }
+                    },
+                    doc! {
+                        "$lookup": {
+                            "from": &self.coll_riddles,
+                            "localField": "_id",
+                            "foreignField": "_id",
+                            "as": "riddle"
+                        }
+                    },
+                    doc! {
+                        "$set": {
+                            "score": { "$arrayElemAt": [ "$riddle.difficulty", 0u32 ] }
+                        }
+                    },
+                    doc! { "$unset": "riddle" },
+                    doc! { "$sort": { "difficulty": -1 } },
+                ],
+                None,
+            )
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let docs: Vec<bson::Document> = match cursor.try_collect().await {
+            Ok(docs) => docs,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let mut ratings: Vec<RiddleRating> = Vec::new();
+        for doc in docs.into_iter() {
+            match bson::from_document(doc) {
+                Ok(rating) => ratings.push(rating),
+                Err(e) => return Err(DatabaseQueryError(e.to_string())),
+            }
+        }
+        Ok(ratings)
+    }
+
+    pub fn get_notes_coll(&self) -> Collection<Note> {
+        self.get_database().collection::<Note>(&self.coll_notes)
+    }
+
+    pub async fn get_note(&self, oid: &ObjectId) -> Result<Note> {
+        let note: Option<Note> = match self
+            .get_notes_coll()
+            .find_one(doc! { "_id": oid }, None)
+            .await
+        {
+            Ok(note) => note,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match note {
+            Some(note) => Ok(note),
+            None => Err(NoteNotFoundError),
+        }
+    }
+
+    async fn find_notes(&self, filter: bson::Document) -> Result<Vec<Note>> {
+        let options = FindOptions::builder().sort(doc! { "timestamp": 1 }).build();
+        let cursor = match self.get_notes_coll().find(filter, options).await {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match cursor.try_collect().await {
+            Ok(notes) => Ok(notes),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Returns the notes in the room with the ID `room_id` that are not hidden.
+    pub async fn get_visible_notes(&self, room_id: &ObjectId) -> Result<Vec<Note>> {
+        self.find_notes(doc! { "room_id": room_id, "hidden": false })
+            .await
+    }
+
+    pub async fn get_reported_notes(&self) -> Result<Vec<Note>> {
+        self.find_notes(doc! { "reports.0": { "$exists": true } })
+            .await
+    }
+
+    /// Counts the notes `username` has written since `timestamp`.
+    pub async fn count_notes_since(&self, username: &String, timestamp: i64) -> Result<u64> {
+        match self
+            .get_notes_coll()
+            .count_documents(
+                doc! { "username": username, "timestamp": { "$gte": timestamp } },
+                None,
+            )
+            .await
+        {
+            Ok(count) => Ok(count),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    pub async fn add_note(&self, note: &Note) -> Result<()> {
+        self.insert_document(self.get_notes_coll(), note).await
+    }
+
+    pub async fn report_note(&self, oid: &ObjectId, username: &String) -> Result<()> {
+        match self
+            .get_notes_coll()
+            .update_one(
+                doc! { "_id": oid },
+                doc! { "$addToSet": { "reports": username } },
+                None,
+            )
+            .await
+        {
+            Ok(result) if result.matched_count == 0 => Err(NoteNotFoundError),
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Hides or shows a note. Either way, pending reports are settled.
+    pub async fn set_note_hidden(&self, oid: &ObjectId, hidden: bool) -> Result<()> {
+        match self
+            .get_notes_coll()
+            .update_one(
+                doc! { "_id": oid },
+                doc! { "$set": { "hidden": hidden, "reports": [] } },
+                None,
+            )
+            .await
+        {
+            Ok(result) if result.matched_count == 0 => Err(NoteNotFoundError),
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    pub async fn get_team(&self, oid: &ObjectId) -> Result<Team> {
+        log::info!("get_team(); oid = {}", oid);
+        let team: Option<Team> = match self
+            .get_teams_coll()
+            .find_one(doc! { "_id": oid }, None)
+            .await
+        {
+            Ok(team) => team,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match team {
+            Some(team) => Ok(team),
+            None => Err(TeamNotFoundError),
+        }
+    }
+
+    /// Returns all teams, best first.
+    pub async fn get_teams_by_score(&self) -> Result<Vec<Team>> {
+        let options = FindOptions::builder()
+            .sort(doc! { "score": -1, "level": -1 })
+            .build();
+        let cursor = match self.get_teams_coll().find(doc! {}, options).await {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match cursor.try_collect().await {
+            Ok(teams) => Ok(teams),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Returns the teams that have invited the user named `username`.
+    pub async fn get_teams_inviting(&self, username: &String) -> Result<Vec<Team>> {
+        let cursor = match self
+            .get_teams_coll()
+            .find(doc! { "invitations": username }, None)
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match cursor.try_collect().await {
+            Ok(teams) => Ok(teams),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Stores a newly founded team. Later changes only touch single fields,
+    /// so they do not overwrite progress made by members in the meantime.
+    pub async fn create_team(&self, team: &Team) -> Result<()> {
+        match self.get_teams_coll().insert_one(team, None).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Invites the user named `invitee` to the team, unless they are already
+    /// a member, and returns the team.
+    pub async fn invite_to_team(&self, team_id: &ObjectId, invitee: &String) -> Result<Team> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let team: Option<Team> = match self
+            .get_teams_coll()
+            .find_one_and_update(
+                doc! { "_id": team_id, "members": { "$ne": invitee } },
+                doc! { "$addToSet": { "invitations": invitee } },
+                options,
+            )
+            .await
+        {
+            Ok(team) => team,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match team {
+            Some(team) => Ok(team),
+            None => self.get_team(team_id).await,
+        }
+    }
+
+    /// Turns the invitation of the user named `username` into a membership
+    /// and returns the team.
+    pub async fn join_team(&self, team_id: &ObjectId, username: &String) -> Result<Team> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let team: Option<Team> = match self
+            .get_teams_coll()
+            .find_one_and_update(
+                doc! { "_id": team_id, "invitations": username },
+                doc! {
+                    "$pull": { "invitations": username },
+                    "$addToSet": { "members": username },
+                },
+                options,
+            )
+            .await
+        {
+            Ok(team) => team,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match team {
+            Some(team) => Ok(team),
+            None => match self.get_team(team_id).await {
+                Ok(_) => Err(NotInvitedError),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    /// Removes the user named `username` from the team and deletes the team
+    /// once it has no members left.
+    pub async fn leave_team(&self, team_id: &ObjectId, username: &String) -> Result<()> {
+        match self
+            .get_teams_coll()
+            .update_one(
+                doc! { "_id": team_id },
+                doc! { "$pull": { "members": username } },
+                None,
+            )
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => return Err(MongoQueryError(e)),
+        }
+        match self
+            .get_teams_coll()
+            .delete_one(doc! { "_id": team_id, "members": { "$size": 0 } }, None)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Keeps `seed` as the seed of the riddle `riddle_id` for the user named
+    /// `username`, unless another request has stored one first. Returns the
+    /// seed kept.
+    pub async fn add_riddle_seed(
+        &self,
+        username: &String,
+        riddle_id: &ObjectId,
+        seed: i64,
+    ) -> Result<i64> {
+        let riddle_seed = RiddleSeed {
+            riddle_id: *riddle_id,
+            seed,
+        };
+        let result: UpdateResult = match self
+            .get_users_coll()
+            .update_one(
+                doc! {
+                    "username": username,
+                    "activated": true,
+                    "riddle_seeds.riddle_id": { "$ne": riddle_id },
+                },
+                doc! { "$push": { "riddle_seeds": bson::to_bson(&riddle_seed).unwrap() } },
+                None,
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        if result.modified_count > 0 {
+            return Ok(seed);
+        }
+        match self.get_user(username).await {
+            Ok(user) => match user.seed_for(riddle_id) {
+                Some(seed) => Ok(seed),
+                None => Err(UserNotFoundError),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Puts the user named `username` into the team `team_id`, unless they
+    /// are in a team already, even if only since a moment ago.
+    pub async fn assign_user_team(&self, username: &String, team_id: &ObjectId) -> Result<()> {
+        let result: UpdateResult = match self
+            .get_users_coll()
+            .update_one(
+                doc! { "username": username, "activated": true, "team_id": null },
+                doc! { "$set": { "team_id": team_id } },
+                None,
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match result.modified_count {
+            0 => Err(AlreadyInTeamError),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn clear_user_team(&self, username: &String) -> Result<()> {
+        match self
+            .get_users_coll()
+            .update_one(
+                doc! { "username": username, "activated": true },
+                doc! { "$set": { "team_id": bson::Bson::Null } },
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// The team counterpart of `set_user_solved`: adds `attempt` to the
+    /// riddles solved by the team and by each of its members, unless the
+    /// team has solved the riddle before, and credits `score` and `level`
+    /// to the team. The updates are atomic, so members solving riddles at
+    /// the same time do not overwrite each other's progress.
+    pub async fn set_team_solved(
+        &self,
+        team: &Team,
+        attempt: &RiddleAttempt,
+        score: i32,
+        level: u32,
+    ) -> Result<()> {
+        let result: UpdateResult = match self
+            .get_teams_coll()
+            .update_one(
+                doc! { "_id": team.id, "solved.riddle_id": { "$ne": attempt.riddle_id } },
+                doc! {
+                    "$push": { "solved": bson::to_bson(attempt).unwrap() },
+                    "$inc": { "score": score },
+                    "$max": { "level": level },
+                },
+                None,
+            )
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        if result.matched_count == 0 {
+            return Ok(());
+        }
+        self.share_team_progress(&team.members, &vec![attempt.clone()], level)
+            .await
+    }
+
+    /// Adds `solved` to the solved riddles of the users named `members`,
+    /// skipping riddles they have already solved.
+    pub async fn share_team_progress(
+        &self,
+        members: &Vec<String>,
+        solved: &Vec<RiddleAttempt>,
+        level: u32,
+    ) -> Result<()> {
+        for attempt in solved.iter() {
+            match self
+                .get_users_coll()
+                .update_many(
+                    doc! {
+                        "username": { "$in": members },
+                        "activated": true,
+                        "solved.riddle_id": { "$ne": attempt.riddle_id },
+                    },
+                    doc! { "$push": { "solved": bson::to_bson(attempt).unwrap() } },
+                    None,
+                )
+                .await
+            {
+                Ok(_) => (),
+                Err(e) => return Err(MongoQueryError(e)),
+            }
+        }
+        match self
+            .get_users_coll()
+            .update_many(
+                doc! { "username": { "$in": members }, "activated": true },
+                doc! { "$max": { "level": level } },
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    /// Deducts `deduction` points from the team's score, but not below zero.
+    pub async fn deduct_team_score(&self, team_id: &ObjectId, deduction: i32) -> Result<()> {
+        match self
+            .get_teams_coll()
+            .update_one(
+                doc! { "_id": team_id },
+                vec![doc! {
+                    "$set": { "score": { "$max": [0, { "$subtract": ["$score", deduction] }] } },
+                }],
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    pub async fn get_game(&self, oid: &ObjectId) -> Result<Game> {
+        log::info!("get_game(); oid = {}", oid);
+        let game: Option<Game> = match self
+            .get_games_coll()
+            .find_one(doc! { "_id": oid }, None)
+            .await
+        {
+            Ok(game) => game,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match game {
+            Some(game) => Ok(game),
+            None => Err(GameNotFoundError),
+        }
+    }
+
+    pub async fn get_games(&self) -> Result<Vec<Game>> {
+        log::info!("get_games()");
+        let cursor: mongodb::Cursor<Game> = match self.get_games_coll().find(doc! {}, None).await {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let games: Vec<Game> = match cursor.try_collect().await {
+            Ok(games) => games,
+            Err(e) => return Err(MongoError(e)),
+        };
+        Ok(games)
+    }
+
+    pub async fn get_rooms_of_game(&self, game_id: &ObjectId) -> Result<Vec<Room>> {
+        log::info!("get_rooms_of_game(); game_id = {}", game_id);
+        let cursor: mongodb::Cursor<Room> = match self
+            .get_rooms_coll()
+            .find(
+                doc! { "game_id": game_id },
+                FindOptions::builder().sort(doc! { "number": 1u32 }).build(),
+            )
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let rooms: Vec<Room> = match cursor.try_collect().await {
+            Ok(rooms) => rooms,
+            Err(e) => return Err(MongoError(e)),
+        };
+        Ok(rooms)
+    }
+
+    pub async fn get_riddles_by_oids(&self, oids: &Vec<ObjectId>) -> Result<Vec<Riddle>> {
+        log::info!("get_riddles_by_oids(); {} oids", oids.len());
+        let cursor: mongodb::Cursor<Riddle> = match self
+            .get_riddles_coll()
+            .find(
+                doc! { "_id": { "$in": oids } },
+                FindOptions::builder().sort(doc! { "level": 1u32 }).build(),
+            )
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let riddles: Vec<Riddle> = match cursor.try_collect().await {
+            Ok(riddles) => riddles,
+            Err(e) => return Err(MongoError(e)),
+        };
+        Ok(riddles)
+    }
+
+    /// Writes the game, its rooms and riddles in one transaction. Documents
+    /// that already exist get a version above both the stored one and the
+    /// one in `game`, `rooms` or `riddles`, so versions never go backwards.
+    pub async fn import_game(
+        &self,
+        game: &mut Game,
+        rooms: &mut Vec<Room>,
+        riddles: &mut Vec<Riddle>,
+    ) -> Result<()> {
+        log::info!(
+            "import_game(); game_id = {}, {} rooms, {} riddles",
+            game.id,
+            rooms.len(),
+            riddles.len()
+        );
+        let mut session: ClientSession = match self.client.start_session(None).await {
+            Ok(session) => session,
+            Err(e) => return Err(MongoError(e)),
+        };
+        match session.start_transaction(None).await {
+            Ok(()) => (),
+            Err(e) => return Err(MongoError(e)),
+        }
+        // If anything goes wrong below, dropping the session aborts the transaction.
+        let mut cursor = match self
+            .get_rooms_coll()
+            .find_with_session(doc! { "game_id": game.id }, None, &mut session)
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let mut old_rooms: Vec<Room> = Vec::new();
+        while let Some(room) = cursor.next(&mut session).await {
+            match room {
+                Ok(room) => old_rooms.push(room),
+                Err(e) => return Err(MongoQueryError(e)),
+            }
+        }
+        // Rooms and riddles of other games must not be taken over by reusing
+        // their IDs.
+        let room_ids: Vec<ObjectId> = rooms.iter().map(|room| room.id).collect();
+        let riddle_ids: Vec<ObjectId> = riddles.iter().map(|riddle| riddle.id).collect();
+        let foreign_rooms: u64 = match self
+            .get_rooms_coll()
+            .count_documents_with_session(
+                doc! {
+                    "$or": [
+                        { "_id": { "$in": &room_ids } },
+                        { "neighbors.riddle_id": { "$in": &riddle_ids } },
+                    ],
+                    "game_id": { "$ne": game.id },
+                },
+                None,
+                &mut session,
+            )
+            .await
+        {
+            Ok(count) => count,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        if foreign_rooms > 0 {
+            return Err(ContentValidationError(
+                "the file reuses IDs of rooms or riddles of another game".to_string(),
+            ));
+        }
+        let old_game: Option<Game> = match self
+            .get_games_coll()
+            .find_one_with_session(doc! { "_id": game.id }, None, &mut session)
+            .await
+        {
+            Ok(old_game) => old_game,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let mut cursor = match self
+            .get_riddles_coll()
+            .find_with_session(doc! { "_id": { "$in": &riddle_ids } }, None, &mut session)
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let mut old_riddles: Vec<Riddle> = Vec::new();
+        while let Some(riddle) = cursor.next(&mut session).await {
+            match riddle {
+                Ok(riddle) => old_riddles.push(riddle),
+                Err(e) => return Err(MongoQueryError(e)),
+            }
+        }
+        if let Some(old_game) = &old_game {
+            game.version = game.version.max(old_game.version) + 1;
+        }
+        for room in rooms.iter_mut() {
+            if let Some(old_room) = old_rooms.iter().find(|old_room| old_room.id == room.id) {
+                room.version = room.version.max(old_room.version) + 1;
+            }
+        }
+        for riddle in riddles.iter_mut() {
+            if let Some(old_riddle) = old_riddles
+                .iter()
+                .find(|old_riddle| old_riddle.id == riddle.id)
+            {
+                riddle.version = riddle.version.max(old_riddle.version) + 1;
+            }
+        }
+        let upsert: ReplaceOptions = ReplaceOptions::builder().upsert(true).build();
+        match self
+            .get_games_coll()
+            .replace_one_with_session(
+                doc! { "_id": game.id },
+                &*game,
+                upsert.clone(),
+                &mut session,
+            )
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => return Err(MongoQueryError(e)),
+        }
+        for riddle in riddles.iter() {
+            match self
+                .get_riddles_coll()
+                .replace_one_with_session(
+                    doc! { "_id": riddle.id },
+                    riddle,
+                    upsert.clone(),
+                    &mut session,
+                )
+                .await
+            {
+                Ok(_) => (),
+                Err(e) => return Err(MongoQueryError(e)),
+            }
+        }
+        for room in rooms.iter() {
+            match self
+                .get_rooms_coll()
+                .replace_one_with_session(
+                    doc! { "_id": room.id },
+                    room,
+                    upsert.clone(),
+                    &mut session,
+                )
+                .await
+            {
+                Ok(_) => (),
+                Err(e) => return Err(MongoQueryError(e)),
+            }
+        }
+        // Rooms and riddles missing from the file are removed, unless a
+        // riddle still guards a door in another game.
+        match self
+            .get_rooms_coll()
+            .delete_many_with_session(
+                doc! { "game_id": game.id, "_id": { "$nin": &room_ids } },
+                None,
+                &mut session,
+            )
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => return Err(MongoQueryError(e)),
+        }
+        let stale_riddle_ids: Vec<ObjectId> = riddle_ids_of_rooms(&old_rooms)
+            .into_iter()
+            .filter(|riddle_id| !riddle_ids.contains(riddle_id))
+            .collect();
+        let shared_riddle_ids: Vec<bson::Bson> = match self
+            .get_rooms_coll()
+            .distinct_with_session(
+                "neighbors.riddle_id",
+                doc! {
+                    "game_id": { "$ne": game.id },
+                    "neighbors.riddle_id": { "$in": &stale_riddle_ids },
+                },
+                None,
+                &mut session,
+            )
+            .await
+        {
+            Ok(shared_riddle_ids) => shared_riddle_ids,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let stale_riddle_ids: Vec<ObjectId> = stale_riddle_ids
+            .into_iter()
+            .filter(|riddle_id| !shared_riddle_ids.contains(&bson::Bson::ObjectId(*riddle_id)))
+            .collect();
+        match self
+            .get_riddles_coll()
+            .delete_many_with_session(
+                doc! { "_id": { "$in": &stale_riddle_ids } },
+                None,
+                &mut session,
+            )
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => return Err(MongoQueryError(e)),
+        }
+        match session.commit_transaction().await {
+            Ok(()) => {
+                log::info!("Imported game {}.", game.id);
+                Ok(())
+            }
+            Err(e) => Err(MongoError(e)),
+        }
+    }
+
+    /// Returns all rooms with a door guarded by the riddle with the ID `riddle_id`.
+    pub async fn get_rooms_by_riddle(&self, riddle_id: &ObjectId) -> Result<Vec<Room>> {
+        log::info!("get_rooms_by_riddle(); riddle_id = {}", riddle_id);
+        let cursor: mongodb::Cursor<Room> = match self
+            .get_rooms_coll()
+            .find(doc! { "neighbors.riddle_id": riddle_id }, None)
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let rooms: Vec<Room> = match cursor.try_collect().await {
+            Ok(rooms) => rooms,
+            Err(e) => return Err(MongoError(e)),
+        };
+        Ok(rooms)
+    }
 
-impl Distribution<u8> for KeyChars {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u8 {
-        const RANGE: usize = 25 + 10;
-        const GEN_ASCII_STR_CHARSET: &[u8; RANGE] = b"abcdefghijkmnopqrstuvwxyz0123456789";
-        loop {
-            let idx: usize = rng.next_u32() as usize % RANGE;
-            return GEN_ASCII_STR_CHARSET[idx];
+    pub async fn insert_document<T: Serialize>(&self, coll: Collection<T>, item: &T) -> Result<()> {
+        match coll.insert_one(item, None).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
         }
     }
-}
 
-#[derive(Clone, Debug)]
-pub struct DB {
-    pub client: Client,
-    pub name: String,
-    pub coll_rooms: String,
-    pub coll_riddles: String,
-    pub coll_users: String,
-}
+    /// Starts a session with a transaction. If the session is dropped before
+    /// `commit_audited` is called, the transaction is aborted.
+    async fn start_transaction(&self) -> Result<ClientSession> {
+        let mut session: ClientSession = match self.client.start_session(None).await {
+            Ok(session) => session,
+            Err(e) => return Err(MongoError(e)),
+        };
+        match session.start_transaction(None).await {
+            Ok(()) => Ok(session),
+            Err(e) => Err(MongoError(e)),
+        }
+    }
 
-impl DB {
-    pub async fn init() -> Result<Self> {
-        let url: String = env::var("DB_URL").expect("DB_URL is not in .env file");
-        let name: String = env::var("DB_NAME").expect("DB_NAME is not in .env file");
-        let coll_users: String =
-            env::var("DB_COLL_USERS").expect("DB_COLL_USERS is not in .env file");
-        let coll_riddles: String =
-            env::var("DB_COLL_RIDDLES").expect("DB_COLL_RIDDLES is not in .env file");
-        let coll_rooms: String =
-            env::var("DB_COLL_ROOMS").expect("DB_COLL_ROOMS is not in .env file");
-        let mut client_options: mongodb::options::ClientOptions =
-            ClientOptions::parse(url).await.unwrap();
-        client_options.app_name = Some(name.to_string());
-        Ok(Self {
-            client: Client::with_options(client_options).unwrap(),
-            name: name.to_string(),
-            coll_users: coll_users.to_string(),
-            coll_riddles: coll_riddles.to_string(),
-            coll_rooms: coll_rooms.to_string(),
-        })
+    /// Adds `audit` to the audit trail and commits the transaction of
+    /// `session`, so that a change is only stored together with its entry.
+    async fn commit_audited(&self, mut session: ClientSession, audit: &AuditEntry) -> Result<()> {
+        match self
+            .get_audit_coll()
+            .insert_one_with_session(audit, None, &mut session)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => return Err(MongoQueryError(e)),
+        }
+        match session.commit_transaction().await {
+            Ok(()) => Ok(()),
+            Err(e) => Err(MongoError(e)),
+        }
     }
 
-    pub fn get_database(&self) -> Database {
-        self.client.database(&self.name)
+    /// Inserts `item` and adds `audit` to the audit trail in one transaction.
+    pub async fn insert_audited<T: Serialize>(
+        &self,
+        coll: Collection<T>,
+        item: &T,
+        audit: &AuditEntry,
+    ) -> Result<()> {
+        let mut session: ClientSession = match self.start_transaction().await {
+            Ok(session) => session,
+            Err(e) => return Err(e),
+        };
+        match coll.insert_one_with_session(item, None, &mut session).await {
+            Ok(_) => (),
+            Err(e) => return Err(MongoQueryError(e)),
+        }
+        self.commit_audited(session, audit).await
     }
 
-    pub fn get_users_coll(&self) -> Collection<User> {
-        self.get_database().collection::<User>(&self.coll_users)
+    /// Replaces the document with the ID `oid` by `item`, but only if the
+    /// stored document still has the version `version`. Otherwise someone
+    /// else has changed it in the meantime. `audit` is added to the audit
+    /// trail in the same transaction.
+    pub async fn replace_audited<T: Serialize>(
+        &self,
+        coll: Collection<T>,
+        oid: &ObjectId,
+        version: u32,
+        item: &T,
+        audit: &AuditEntry,
+    ) -> Result<()> {
+        log::info!("replace_audited(); oid = {}, version = {}", oid, version);
+        let mut session: ClientSession = match self.start_transaction().await {
+            Ok(session) => session,
+            Err(e) => return Err(e),
+        };
+        let result: UpdateResult = match coll
+            .replace_one_with_session(versioned(oid, version), item, None, &mut session)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        if result.matched_count == 0 {
+            return Err(VersionConflictError);
+        }
+        self.commit_audited(session, audit).await
     }
 
-    pub fn get_riddles_coll(&self) -> Collection<Riddle> {
-        self.get_database().collection::<Riddle>(&self.coll_riddles)
+    /// Deletes the document with the ID `oid` if it still has the version
+    /// `version` and adds `audit` to the audit trail in one transaction.
+    pub async fn delete_audited<T>(
+        &self,
+        coll: Collection<T>,
+        oid: &ObjectId,
+        version: u32,
+        audit: &AuditEntry,
+    ) -> Result<()> {
+        log::info!("delete_audited(); oid = {}, version = {}", oid, version);
+        let mut session: ClientSession = match self.start_transaction().await {
+            Ok(session) => session,
+            Err(e) => return Err(e),
+        };
+        let result: DeleteResult = match coll
+            .delete_one_with_session(versioned(oid, version), None, &mut session)
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        if result.deleted_count == 0 {
+            return Err(VersionConflictError);
+        }
+        self.commit_audited(session, audit).await
     }
 
-    pub fn get_rooms_coll(&self) -> Collection<Room> {
-        self.get_database().collection::<Room>(&self.coll_rooms)
+    pub async fn get_audit_entries(&self, limit: i64) -> Result<Vec<AuditEntry>> {
+        log::info!("get_audit_entries(); limit = {}", limit);
+        let cursor: mongodb::Cursor<AuditEntry> = match self
+            .get_audit_coll()
+            .find(
+                doc! {},
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": -1i32 })
+                    .limit(limit)
+                    .build(),
+            )
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        let entries: Vec<AuditEntry> = match cursor.try_collect().await {
+            Ok(entries) => entries,
+            Err(e) => return Err(MongoError(e)),
+        };
+        Ok(entries)
     }
 
     pub async fn get_num_rooms(&self, game_id: &ObjectId) -> Result<u32> {
@@ -450,6 +1812,35 @@ impl DB {
         Ok(users)
     }
 
+    /// Returns the players who have finished the game `game_id`.
+    pub async fn get_game_finishers(&self, game_id: &ObjectId) -> Result<Vec<UserSolvedData>> {
+        log::info!("get_game_finishers(); game_id = {}", game_id);
+        let cursor: mongodb::Cursor<UserSolvedData> = match self
+            .get_database()
+            .collection::<UserSolvedData>(&self.coll_users)
+            .find(
+                doc! {
+                    "activated": true,
+                    "finished.game_id": game_id,
+                },
+                FindOptions::builder()
+                    .projection(doc! {
+                        "username": 1u32,
+                        "solved.riddle_id": 1u32,
+                    })
+                    .build(),
+            )
+            .await
+        {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        match cursor.try_collect().await {
+            Ok(users) => Ok(users),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
     pub async fn get_max_score_for_game(&self, game_id: &ObjectId) -> Result<u32> {
         log::info!("get_max_score(); game_id = {}", game_id);
         let mut cursor: mongodb::Cursor<bson::Document> = match self
@@ -464,6 +1855,11 @@ impl DB {
                     doc! {
                         "$unwind": "$neighbors",
                     },
+                    doc! {
+                        "$match": {
+                            "neighbors.riddle_id": { "$ne": bson::Bson::Null },
+                        }
+                    },
                     doc! {
                         "$group": {
                             "_id": "$neighbors.riddle_id",
@@ -525,6 +1921,11 @@ impl DB {
                     doc! {
                         "$unwind": "$neighbors",
                     },
+                    doc! {
+                        "$match": {
+                            "neighbors.riddle_id": { "$ne": bson::Bson::Null },
+                        }
+                    },
                     doc! {
                         "$group": {
                             "_id": "$neighbors.riddle_id",
@@ -666,7 +2067,7 @@ impl DB {
         let found: &Direction = match room
             .neighbors
             .iter()
-            .find(|neighbor| neighbor.riddle_id == *oid)
+            .find(|neighbor| neighbor.riddle_id == Some(*oid))
         {
             Some(neighbor) => neighbor,
             None => {
@@ -677,7 +2078,7 @@ impl DB {
                 );
             }
         };
-        (Some(found.riddle_id), Some(user), Option::default())
+        (found.riddle_id, Some(user), Option::default())
     }
 
     pub async fn is_username_or_email_taken(
@@ -785,13 +2186,17 @@ impl DB {
         }
     }
 
+    /// Finds the room of the game `room` belongs to whose door in the
+    /// direction `opposite` is guarded by the riddle `riddle_id`.
     pub async fn get_room_behind(
         &self,
+        room: &Room,
         opposite: &String,
         riddle_id: &bson::oid::ObjectId,
     ) -> Result<Room> {
         log::info!(
-            "get_room_behind(); opposite = {}, riddle_id = {}",
+            "get_room_behind(); room_id = {}, opposite = {}, riddle_id = {}",
+            &room.id,
             opposite,
             riddle_id
         );
@@ -799,6 +2204,8 @@ impl DB {
             .get_rooms_coll()
             .find_one(
                 doc! {
+                    "_id": { "$ne": room.id },
+                    "game_id": room.game_id,
                     "neighbors": {
                         "$elemMatch": {
                             "direction": opposite,
@@ -819,6 +2226,47 @@ impl DB {
         }
     }
 
+    /// Tells whether doors of other rooms lead into `room`, either directly
+    /// or as counterparts of its doors, or whether players are in it.
+    pub async fn is_room_referenced(&self, room: &Room) -> Result<bool> {
+        log::info!("is_room_referenced(); room_id = {}", &room.id);
+        let mut references: Vec<bson::Document> = vec![doc! { "neighbors.target": room.id }];
+        for direction in room.neighbors.iter() {
+            if let (Some(riddle_id), Some(counterpart)) =
+                (direction.riddle_id, direction.counterpart())
+            {
+                references.push(doc! {
+                    "neighbors": {
+                        "$elemMatch": {
+                            "direction": counterpart,
+                            "riddle_id": riddle_id,
+                        }
+                    }
+                });
+            }
+        }
+        let rooms: u64 = match self
+            .get_rooms_coll()
+            .count_documents(doc! { "_id": { "$ne": room.id }, "$or": references }, None)
+            .await
+        {
+            Ok(count) => count,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        if rooms > 0 {
+            return Ok(true);
+        }
+        let users: u64 = match self
+            .get_users_coll()
+            .count_documents(doc! { "in_room": room.id }, None)
+            .await
+        {
+            Ok(count) => count,
+            Err(e) => return Err(MongoQueryError(e)),
+        };
+        Ok(users > 0)
+    }
+
     pub async fn get_user_with_pin(&self, username: &String, pin: PinType) -> Result<User> {
         log::info!("get_user_with_pin(\"{}\", \"{:06}\")", username, pin);
         let result: Option<User> = match self
@@ -844,20 +2292,64 @@ impl DB {
         }
     }
 
+    /// Adds `attempt` to the user's solved riddles, credits `score` and raises
+    /// the user's level to `level`. Riddles the user has already solved,
+    /// e.g. shared by a teammate in the meantime, are left untouched. Returns
+    /// whether the riddle has been credited.
     pub async fn set_user_solved(
         &mut self,
-        solutions: &Vec<RiddleAttempt>,
+        attempt: &RiddleAttempt,
+        score: i32,
+        level: u32,
         user: &User,
-    ) -> Result<()> {
+    ) -> Result<bool> {
+        match self
+            .get_users_coll()
+            .update_one(
+                doc! {
+                    "_id": user.id,
+                    "activated": true,
+                    "solved.riddle_id": { "$ne": attempt.riddle_id },
+                },
+                doc! {
+                    "$push": { "solved": bson::to_bson(attempt).unwrap() },
+                    "$inc": { "score": score },
+                    "$max": { "level": level },
+                },
+                None,
+            )
+            .await
+        {
+            Ok(result) => Ok(result.modified_count > 0),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    pub async fn set_user_in_room(&self, user: &User) -> Result<()> {
+        match self
+            .get_users_coll()
+            .update_one(
+                doc! { "_id": user.id, "activated": true },
+                doc! { "$set": { "in_room": user.in_room } },
+                None,
+            )
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MongoQueryError(e)),
+        }
+    }
+
+    pub async fn set_user_items(&self, user: &User) -> Result<()> {
         match self
             .get_users_coll()
             .update_one(
                 doc! { "_id": user.id, "activated": true },
                 doc! {
                     "$set": {
-                        "solved": bson::to_bson(solutions).unwrap(),
-                        "level": user.level,
-                        "score": user.score,
+                        "inventory": bson::to_bson(&user.inventory).unwrap(),
+                        "items_taken": bson::to_bson(&user.items_taken).unwrap(),
+                        "items_dropped": bson::to_bson(&user.items_dropped).unwrap(),
                     },
                 },
                 None,
@@ -24,6 +24,14 @@ pub enum Error {
     DatabaseQueryError(String),
     #[error("script environment error")]
     ScriptEnvironmentError,
+    #[error("riddle script error: {0}")]
+    ScriptError(String),
+    #[error("invalid riddle script: {0}")]
+    ScriptValidationError(String),
+    #[error("riddle script took too long")]
+    ScriptTimeoutError,
+    #[error("attachment not found")]
+    AttachmentNotFoundError,
     #[error("hashing error")]
     HashingError,
     #[error("password must be at least 8 characters long")]
@@ -56,6 +64,28 @@ pub enum Error {
     RiddleNotFoundError,
     #[error("room not found")]
     RoomNotFoundError,
+    #[error("there is no such thing here")]
+    ObjectNotFoundError,
+    #[error("game not found")]
+    GameNotFoundError,
+    #[error("game not finished")]
+    GameNotFinishedError,
+    #[error("certificate cannot be serialized")]
+    CertificateSerializationError,
+    #[error("game file error: {0}")]
+    GameFileError(String),
+    #[error("unknown command")]
+    UnknownCommandError,
+    #[error("game validation failed")]
+    GameValidationError,
+    #[error("cannot generate labyrinth: {0}")]
+    GeneratorError(String),
+    #[error("document has been changed by someone else")]
+    VersionConflictError,
+    #[error("room is still referenced by doors or players")]
+    RoomInUseError,
+    #[error("invalid content: {0}")]
+    ContentValidationError(String),
     #[error("user is in no room")]
     UserIsInNoRoom,
     #[error("riddle has not been seen")]
@@ -66,6 +96,34 @@ pub enum Error {
     NeighborNotFoundError,
     #[error("room behind not found")]
     RoomBehindNotFoundError,
+    #[error("door is locked")]
+    DoorLockedError,
+    #[error("item required")]
+    ItemRequiredError,
+    #[error("item not found")]
+    ItemNotFoundError,
+    #[error("room has not been visited")]
+    RoomNotVisitedError,
+    #[error("no route found")]
+    RouteNotFoundError,
+    #[error("team not found")]
+    TeamNotFoundError,
+    #[error("invalid team name")]
+    InvalidTeamNameError,
+    #[error("user is already in a team")]
+    AlreadyInTeamError,
+    #[error("user is in no team")]
+    NotInTeamError,
+    #[error("user has not been invited")]
+    NotInvitedError,
+    #[error("note not found")]
+    NoteNotFoundError,
+    #[error("ratings must be between 1 and 5")]
+    InvalidRatingError,
+    #[error("note is empty or too long")]
+    NoteLengthError,
+    #[error("too many notes")]
+    NoteRateLimitError,
     #[error("riddle not solved")]
     RiddleNotSolvedError,
     #[error("wrong credentials")]
@@ -117,8 +175,15 @@ pub async fn handle_rejection(err: Rejection) -> std::result::Result<impl Reply,
     } else if let Some(e) = err.find::<Error>() {
         match e {
             Error::CheatError => (StatusCode::PAYMENT_REQUIRED, e.to_string()),
+            Error::GameNotFoundError => (StatusCode::NOT_FOUND, e.to_string()),
+            Error::RoomNotFoundError => (StatusCode::NOT_FOUND, e.to_string()),
+            Error::TeamNotFoundError => (StatusCode::NOT_FOUND, e.to_string()),
+            Error::NoteNotFoundError => (StatusCode::NOT_FOUND, e.to_string()),
             Error::RoomBehindNotFoundError => (StatusCode::CONFLICT, e.to_string()),
             Error::NeighborNotFoundError => (StatusCode::CONFLICT, e.to_string()),
+            Error::VersionConflictError => (StatusCode::CONFLICT, e.to_string()),
+            Error::RoomInUseError => (StatusCode::CONFLICT, e.to_string()),
+            Error::NoteRateLimitError => (StatusCode::TOO_MANY_REQUESTS, e.to_string()),
             Error::UnsafePasswordError => (StatusCode::CONFLICT, e.to_string()),
             Error::InvalidEmailError => (StatusCode::CONFLICT, e.to_string()),
             Error::InvalidUsernameError => (StatusCode::CONFLICT, e.to_string()),
@@ -126,6 +191,8 @@ pub async fn handle_rejection(err: Rejection) -> std::result::Result<impl Reply,
             Error::WrongCredentialsError => (StatusCode::FORBIDDEN, e.to_string()),
             Error::NoPermissionError => (StatusCode::UNAUTHORIZED, e.to_string()),
             Error::JWTTokenError => (StatusCode::UNAUTHORIZED, e.to_string()),
+            Error::ScriptError(_) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            Error::ScriptTimeoutError => (StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
             Error::JWTTokenCreationError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Internal Server Error".to_string(),
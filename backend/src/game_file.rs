@@ -0,0 +1,404 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::db::{
+    riddle_ids_of_rooms, Direction, DoorCondition, DoorType, Game, Item, Riddle, Room, RoomObject,
+    UploadedFile, DB,
+};
+use crate::i18n::LocalizedText;
+use crate::scripting::{validate_script, ScriptEngine};
+use crate::validator::{self, ValidationIssue};
+use crate::{error::Error, oid, Result};
+use bson::oid::ObjectId;
+use log;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A complete game (labyrinth) in a format suitable for editing by hand
+/// and for keeping under version control. Object IDs are written as plain
+/// hex strings so that the documents in the database can be recreated
+/// one-to-one.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GameFile {
+    pub game: GameEntry,
+    #[serde(default)]
+    pub rooms: Vec<RoomEntry>,
+    #[serde(default)]
+    pub riddles: Vec<RiddleEntry>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GameEntry {
+    #[serde(with = "oid")]
+    pub id: ObjectId,
+    #[serde(default)]
+    pub version: u32,
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DirectionEntry {
+    pub direction: String,
+    #[serde(default, with = "oid::option", skip_serializing_if = "Option::is_none")]
+    pub riddle_id: Option<ObjectId>,
+    #[serde(default)]
+    pub level: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counterpart: Option<String>,
+    #[serde(default, skip_serializing_if = "is_regular")]
+    pub door: DoorType,
+    #[serde(default, with = "oid::option", skip_serializing_if = "Option::is_none")]
+    pub target: Option<ObjectId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<ConditionEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ConditionEntry {
+    #[serde(default, with = "oid::vec", skip_serializing_if = "Vec::is_empty")]
+    pub riddles: Vec<ObjectId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u32>,
+}
+
+fn is_regular(door: &DoorType) -> bool {
+    door == &DoorType::Regular
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RoomEntry {
+    #[serde(with = "oid")]
+    pub id: ObjectId,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub number: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coords: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit: Option<bool>,
+    #[serde(default)]
+    pub neighbors: Vec<DirectionEntry>,
+    #[serde(default, skip_serializing_if = "LocalizedText::is_empty")]
+    pub title: LocalizedText,
+    #[serde(default, skip_serializing_if = "LocalizedText::is_empty")]
+    pub description: LocalizedText,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solved_description: Option<LocalizedText>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ascii_art: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub objects: Vec<RoomObject>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<Item>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RiddleEntry {
+    #[serde(with = "oid")]
+    pub id: ObjectId,
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub level: u32,
+    #[serde(default)]
+    pub difficulty: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deduction: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_case: Option<bool>,
+    #[serde(default)]
+    pub solution: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debriefing: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credits: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<UploadedFile>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_engine: Option<ScriptEngine>,
+    #[serde(default)]
+    pub external_password_input: bool,
+}
+
+impl From<Direction> for DirectionEntry {
+    fn from(direction: Direction) -> Self {
+        DirectionEntry {
+            direction: direction.direction,
+            riddle_id: direction.riddle_id,
+            level: direction.level,
+            counterpart: direction.counterpart,
+            door: direction.door,
+            target: direction.target,
+            condition: direction.condition.map(|condition| ConditionEntry {
+                riddles: condition.riddles,
+                level: condition.level,
+            }),
+            item: direction.item,
+        }
+    }
+}
+
+impl From<DirectionEntry> for Direction {
+    fn from(entry: DirectionEntry) -> Self {
+        Direction {
+            direction: entry.direction,
+            riddle_id: entry.riddle_id,
+            level: entry.level,
+            counterpart: entry.counterpart,
+            door: entry.door,
+            target: entry.target,
+            condition: entry.condition.map(|condition| DoorCondition {
+                riddles: condition.riddles,
+                level: condition.level,
+            }),
+            item: entry.item,
+        }
+    }
+}
+
+impl From<Room> for RoomEntry {
+    fn from(room: Room) -> Self {
+        RoomEntry {
+            id: room.id,
+            version: room.version,
+            number: room.number,
+            coords: room.coords,
+            entry: room.entry,
+            exit: room.exit,
+            neighbors: room
+                .neighbors
+                .into_iter()
+                .map(DirectionEntry::from)
+                .collect(),
+            title: room.title,
+            description: room.description,
+            solved_description: room.solved_description,
+            ascii_art: room.ascii_art,
+            objects: room.objects,
+            items: room.items,
+        }
+    }
+}
+
+impl RoomEntry {
+    pub fn into_room(self, game_id: &ObjectId) -> Room {
+        Room {
+            id: self.id,
+            version: self.version,
+            number: self.number,
+            coords: self.coords,
+            neighbors: self.neighbors.into_iter().map(Direction::from).collect(),
+            game_id: *game_id,
+            entry: self.entry,
+            exit: self.exit,
+            title: self.title,
+            description: self.description,
+            solved_description: self.solved_description,
+            ascii_art: self.ascii_art,
+            objects: self.objects,
+            items: self.items,
+        }
+    }
+}
+
+impl From<Riddle> for RiddleEntry {
+    fn from(riddle: Riddle) -> Self {
+        RiddleEntry {
+            id: riddle.id,
+            version: riddle.version,
+            level: riddle.level,
+            difficulty: riddle.difficulty,
+            deduction: riddle.deduction,
+            ignore_case: riddle.ignore_case,
+            solution: riddle.solution,
+            task: riddle.task,
+            debriefing: riddle.debriefing,
+            credits: riddle.credits,
+            files: riddle.files,
+            script: riddle.script,
+            script_engine: riddle.script_engine,
+            external_password_input: riddle.external_password_input,
+        }
+    }
+}
+
+impl From<RiddleEntry> for Riddle {
+    fn from(entry: RiddleEntry) -> Self {
+        Riddle {
+            id: entry.id,
+            version: entry.version,
+            difficulty: entry.difficulty,
+            deduction: entry.deduction,
+            level: entry.level,
+            files: entry.files,
+            ignore_case: entry.ignore_case,
+            solution: entry.solution,
+            debriefing: entry.debriefing,
+            task: entry.task,
+            credits: entry.credits,
+            script: entry.script,
+            script_engine: entry.script_engine,
+            external_password_input: entry.external_password_input,
+        }
+    }
+}
+
+fn is_json(path: &str) -> bool {
+    match Path::new(path).extension() {
+        Some(ext) => ext.eq_ignore_ascii_case("json"),
+        None => false,
+    }
+}
+
+/// Reads a YAML or JSON file. Files ending in `.json` are parsed as JSON,
+/// everything else as YAML.
+fn read_file<T: DeserializeOwned>(path: &str) -> Result<T> {
+    log::info!("read_file(); path = {}", path);
+    let contents: String = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => return Err(Error::GameFileError(e.to_string())),
+    };
+    match is_json(path) {
+        true => match serde_json::from_str(&contents) {
+            Ok(value) => Ok(value),
+            Err(e) => Err(Error::GameFileError(e.to_string())),
+        },
+        false => match serde_yaml::from_str(&contents) {
+            Ok(value) => Ok(value),
+            Err(e) => Err(Error::GameFileError(e.to_string())),
+        },
+    }
+}
+
+/// Only the riddles of a game file. Any other content of the file is ignored,
+/// so an exported game can serve as a riddle pool for the generator.
+#[derive(Deserialize, Debug)]
+struct RiddlePool {
+    #[serde(default)]
+    riddles: Vec<RiddleEntry>,
+}
+
+pub fn read_riddles(path: &str) -> Result<Vec<Riddle>> {
+    let pool: RiddlePool = match read_file(path) {
+        Ok(pool) => pool,
+        Err(e) => return Err(e),
+    };
+    Ok(pool.riddles.into_iter().map(Riddle::from).collect())
+}
+
+impl GameFile {
+    pub fn read(path: &str) -> Result<GameFile> {
+        read_file(path)
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        log::info!("GameFile::write(); path = {}", path);
+        let contents: String = match is_json(path) {
+            true => match serde_json::to_string_pretty(self) {
+                Ok(contents) => contents,
+                Err(e) => return Err(Error::GameFileError(e.to_string())),
+            },
+            false => match serde_yaml::to_string(self) {
+                Ok(contents) => contents,
+                Err(e) => return Err(Error::GameFileError(e.to_string())),
+            },
+        };
+        match fs::write(path, contents) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Error::GameFileError(e.to_string())),
+        }
+    }
+
+    pub fn into_parts(self) -> (Game, Vec<Room>, Vec<Riddle>) {
+        let game = Game {
+            id: self.game.id,
+            version: self.game.version,
+            name: self.game.name,
+        };
+        let rooms: Vec<Room> = self
+            .rooms
+            .into_iter()
+            .map(|room| room.into_room(&game.id))
+            .collect();
+        let riddles: Vec<Riddle> = self.riddles.into_iter().map(Riddle::from).collect();
+        (game, rooms, riddles)
+    }
+
+    pub fn from_parts(game: Game, rooms: Vec<Room>, riddles: Vec<Riddle>) -> GameFile {
+        GameFile {
+            game: GameEntry {
+                id: game.id,
+                version: game.version,
+                name: game.name,
+            },
+            rooms: rooms.into_iter().map(RoomEntry::from).collect(),
+            riddles: riddles.into_iter().map(RiddleEntry::from).collect(),
+        }
+    }
+}
+
+/// Upserts the game, its rooms and all riddles from the file at `path`
+/// in one transaction. The labyrinth and the riddle scripts are validated
+/// first; the file is rejected if the validator finds any issues.
+pub async fn import(db: &DB, path: &str) -> Result<()> {
+    let game_file: GameFile = match GameFile::read(path) {
+        Ok(game_file) => game_file,
+        Err(e) => return Err(e),
+    };
+    let (mut game, mut rooms, mut riddles) = game_file.into_parts();
+    let issues: Vec<ValidationIssue> = validator::validate_game(&game, &rooms, &riddles);
+    if !issues.is_empty() {
+        return Err(Error::ContentValidationError(
+            issues
+                .iter()
+                .map(|issue| issue.message.clone())
+                .collect::<Vec<String>>()
+                .join("; "),
+        ));
+    }
+    for riddle in riddles.iter() {
+        if let Some(ref script) = riddle.script {
+            match validate_script(riddle.script_engine.unwrap_or_default(), script).await {
+                Ok(()) => (),
+                Err(Error::ScriptValidationError(message)) => {
+                    return Err(Error::ScriptValidationError(format!(
+                        "riddle {}: {}",
+                        riddle.id, message
+                    )))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    db.import_game(&mut game, &mut rooms, &mut riddles).await
+}
+
+/// Writes the game with the ID `game_id`, its rooms and all riddles
+/// behind the rooms' doors to the file at `path`.
+pub async fn export(db: &DB, game_id: &ObjectId, path: &str) -> Result<()> {
+    let game: Game = match db.get_game(game_id).await {
+        Ok(game) => game,
+        Err(e) => return Err(e),
+    };
+    let rooms: Vec<Room> = match db.get_rooms_of_game(game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(e),
+    };
+    let riddle_ids: Vec<ObjectId> = riddle_ids_of_rooms(&rooms);
+    let riddles: Vec<Riddle> = match db.get_riddles_by_oids(&riddle_ids).await {
+        Ok(riddles) => riddles,
+        Err(e) => return Err(e),
+    };
+    GameFile::from_parts(game, rooms, riddles).write(path)
+}
@@ -0,0 +1,362 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::db::{Direction, DoorType, Game, Riddle, Room};
+use crate::game_file::GameFile;
+use crate::i18n::LocalizedText;
+use crate::{error::Error, Result};
+use bson::oid::ObjectId;
+use log;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::{BTreeSet, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    /// Exactly one path between any two rooms.
+    Perfect,
+    /// No dead ends: every room has at least two doors.
+    Braided,
+    /// A perfect maze with some additional doors creating loops.
+    Loops,
+}
+
+impl Topology {
+    pub fn from_str(topology: &str) -> Option<Topology> {
+        match topology {
+            "perfect" => Some(Topology::Perfect),
+            "braided" => Some(Topology::Braided),
+            "loops" => Some(Topology::Loops),
+            _ => Option::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub topology: Topology,
+    pub seed: u64,
+}
+
+const DIRECTIONS: [(&str, i64, i64); 4] = [("n", 0, -1), ("e", 1, 0), ("s", 0, 1), ("w", -1, 0)];
+
+struct Maze {
+    width: usize,
+    height: usize,
+    /// Passages between two cells, stored as `(a, b)` with `a < b`.
+    passages: BTreeSet<(usize, usize)>,
+}
+
+impl Maze {
+    fn new(width: usize, height: usize) -> Maze {
+        Maze {
+            width,
+            height,
+            passages: BTreeSet::new(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// All cells adjacent to `cell` on the grid, regardless of walls.
+    fn adjacent(&self, cell: usize) -> Vec<(&'static str, usize)> {
+        let x = (cell % self.width) as i64;
+        let y = (cell / self.width) as i64;
+        DIRECTIONS
+            .iter()
+            .filter(|(_, dx, dy)| {
+                x + dx >= 0
+                    && x + dx < self.width as i64
+                    && y + dy >= 0
+                    && y + dy < self.height as i64
+            })
+            .map(|(direction, dx, dy)| {
+                (*direction, ((y + dy) * self.width as i64 + x + dx) as usize)
+            })
+            .collect()
+    }
+
+    fn connect(&mut self, a: usize, b: usize) {
+        self.passages.insert((a.min(b), a.max(b)));
+    }
+
+    fn connected(&self, a: usize, b: usize) -> bool {
+        self.passages.contains(&(a.min(b), a.max(b)))
+    }
+
+    fn doors(&self, cell: usize) -> Vec<usize> {
+        self.adjacent(cell)
+            .into_iter()
+            .map(|(_, other)| other)
+            .filter(|other| self.connected(cell, *other))
+            .collect()
+    }
+
+    /// Carves a perfect maze with a randomized depth-first search.
+    fn carve(&mut self, rng: &mut StdRng) {
+        let mut visited: Vec<bool> = vec![false; self.size()];
+        let mut stack: Vec<usize> = vec![0];
+        visited[0] = true;
+        while let Some(&cell) = stack.last() {
+            let unvisited: Vec<usize> = self
+                .adjacent(cell)
+                .into_iter()
+                .map(|(_, other)| other)
+                .filter(|other| !visited[*other])
+                .collect();
+            match unvisited.choose(rng) {
+                Some(&next) => {
+                    self.connect(cell, next);
+                    visited[next] = true;
+                    stack.push(next);
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    /// Removes all dead ends by opening an additional wall in each of them,
+    /// preferably towards another dead end.
+    fn braid(&mut self, rng: &mut StdRng) {
+        let mut cells: Vec<usize> = (0..self.size()).collect();
+        cells.shuffle(rng);
+        for cell in cells {
+            if self.doors(cell).len() != 1 {
+                continue;
+            }
+            let walls: Vec<usize> = self
+                .adjacent(cell)
+                .into_iter()
+                .map(|(_, other)| other)
+                .filter(|other| !self.connected(cell, *other))
+                .collect();
+            let dead_ends: Vec<usize> = walls
+                .iter()
+                .copied()
+                .filter(|other| self.doors(*other).len() == 1)
+                .collect();
+            let candidates: &Vec<usize> = match dead_ends.is_empty() {
+                true => &walls,
+                false => &dead_ends,
+            };
+            if let Some(&other) = candidates.choose(rng) {
+                self.connect(cell, other);
+            }
+        }
+    }
+
+    /// Opens about one wall in ten rooms to create loops.
+    fn add_loops(&mut self, rng: &mut StdRng) {
+        let mut walls: Vec<(usize, usize)> = Vec::new();
+        for cell in 0..self.size() {
+            for (_, other) in self.adjacent(cell) {
+                if cell < other && !self.connected(cell, other) {
+                    walls.push((cell, other));
+                }
+            }
+        }
+        walls.shuffle(rng);
+        let count: usize = (self.size() / 10).max(1);
+        for (a, b) in walls.into_iter().take(count) {
+            self.connect(a, b);
+        }
+    }
+
+    /// Breadth-first search from `start`. Returns the distance of every cell
+    /// and the cell it was reached from.
+    fn distances(&self, start: usize) -> (Vec<usize>, Vec<Option<usize>>) {
+        let mut distance: Vec<usize> = vec![usize::MAX; self.size()];
+        let mut parent: Vec<Option<usize>> = vec![Option::default(); self.size()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        distance[start] = 0;
+        queue.push_back(start);
+        while let Some(cell) = queue.pop_front() {
+            for other in self.doors(cell) {
+                if distance[other] == usize::MAX {
+                    distance[other] = distance[cell] + 1;
+                    parent[other] = Some(cell);
+                    queue.push_back(other);
+                }
+            }
+        }
+        (distance, parent)
+    }
+}
+
+/// Spreadsheet-like coordinates: columns are lettered A, B, …, Z, AA, AB, …
+/// from west to east, rows are numbered from north to south starting at 1.
+pub fn coords_name(x: usize, y: usize) -> String {
+    let mut column: String = String::new();
+    let mut n: usize = x + 1;
+    while n > 0 {
+        n -= 1;
+        column.insert(0, (b'A' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    format!("{}{}", column, y + 1)
+}
+
+/// The inverse of `coords_name`. Returns `None` if `coords` is not in
+/// spreadsheet-like notation.
+pub fn parse_coords(coords: &str) -> Option<(usize, usize)> {
+    let split: usize = coords.find(|c: char| c.is_ascii_digit())?;
+    let (column, row) = coords.split_at(split);
+    if column.is_empty() || !column.chars().all(|c| c.is_ascii_uppercase()) {
+        return Option::default();
+    }
+    let x: usize = column
+        .bytes()
+        .fold(0, |x, c| x * 26 + (c - b'A') as usize + 1);
+    match row.parse::<usize>() {
+        Ok(y) if y > 0 => Some((x - 1, y - 1)),
+        _ => Option::default(),
+    }
+}
+
+/// Generates a labyrinth according to `options` and assigns riddles from
+/// `pool` to its doors. The riddles are sorted by level and difficulty; the
+/// doors on the shortest path from entry to exit get riddles evenly spread
+/// over that order, so that they get harder the closer one gets to the exit.
+/// All other doors get the remaining riddles ordered by their distance from
+/// the entry. Generating with the same options and pool always yields the
+/// same labyrinth, apart from the object IDs. The riddles get new IDs, so
+/// that the generated game does not share riddles with the game the pool
+/// was taken from.
+pub fn generate(options: &GeneratorOptions, pool: Vec<Riddle>) -> Result<GameFile> {
+    log::info!(
+        "generate(); {}x{} {:?}, seed = {}",
+        options.width,
+        options.height,
+        options.topology,
+        options.seed
+    );
+    if options.width == 0 || options.height == 0 || options.width * options.height < 2 {
+        return Err(Error::GeneratorError(
+            "labyrinth must have at least two rooms".to_string(),
+        ));
+    }
+    let mut rng: StdRng = StdRng::seed_from_u64(options.seed);
+    let mut maze: Maze = Maze::new(options.width, options.height);
+    maze.carve(&mut rng);
+    match options.topology {
+        Topology::Perfect => (),
+        Topology::Braided => maze.braid(&mut rng),
+        Topology::Loops => maze.add_loops(&mut rng),
+    }
+    let entry: usize = 0;
+    let (distance, parent) = maze.distances(entry);
+    // The exit is the room farthest away from the entry.
+    let exit: usize = (0..maze.size())
+        .max_by_key(|cell| (distance[*cell], usize::MAX - cell))
+        .unwrap_or(entry);
+    let mut path: Vec<(usize, usize)> = Vec::new();
+    let mut cell: usize = exit;
+    while let Some(previous) = parent[cell] {
+        path.push((previous.min(cell), previous.max(cell)));
+        cell = previous;
+    }
+    path.reverse();
+    let mut side_passages: Vec<(usize, usize)> = maze
+        .passages
+        .iter()
+        .copied()
+        .filter(|passage| !path.contains(passage))
+        .collect();
+    side_passages.sort_by_key(|(a, b)| (distance[*a].min(distance[*b]), *a, *b));
+    let door_count: usize = maze.passages.len();
+    if pool.len() < door_count {
+        return Err(Error::GeneratorError(format!(
+            "riddle pool contains {} riddles, but the labyrinth has {} doors",
+            pool.len(),
+            door_count
+        )));
+    }
+    let mut riddles: Vec<Riddle> = pool;
+    riddles.sort_by_key(|riddle| (riddle.level, riddle.difficulty));
+    riddles.truncate(door_count);
+    for riddle in riddles.iter_mut() {
+        riddle.id = ObjectId::new();
+        riddle.version = 0;
+    }
+    let mut taken: Vec<bool> = vec![false; door_count];
+    let mut assignment: Vec<((usize, usize), usize)> = Vec::new();
+    for (i, passage) in path.iter().enumerate() {
+        let idx: usize = match path.len() {
+            1 => 0,
+            n => i * (door_count - 1) / (n - 1),
+        };
+        taken[idx] = true;
+        assignment.push((*passage, idx));
+    }
+    let mut remaining = (0..door_count).filter(|idx| !taken[*idx]);
+    for passage in side_passages.iter() {
+        if let Some(idx) = remaining.next() {
+            assignment.push((*passage, idx));
+        }
+    }
+    let game = Game {
+        id: ObjectId::new(),
+        version: 0,
+        name: options.name.clone(),
+    };
+    let mut rooms: Vec<Room> = (0..maze.size())
+        .map(|cell| Room {
+            id: ObjectId::new(),
+            version: 0,
+            number: cell as u32 + 1,
+            coords: Some(coords_name(cell % maze.width, cell / maze.width)),
+            neighbors: Vec::new(),
+            game_id: game.id,
+            entry: match cell == entry {
+                true => Some(true),
+                false => Option::default(),
+            },
+            exit: match cell == exit {
+                true => Some(true),
+                false => Option::default(),
+            },
+            title: LocalizedText::new(),
+            description: LocalizedText::new(),
+            solved_description: Option::default(),
+            ascii_art: Option::default(),
+            objects: Vec::new(),
+            items: Vec::new(),
+        })
+        .collect();
+    for ((a, b), idx) in assignment.iter() {
+        let riddle: &Riddle = &riddles[*idx];
+        for (from, to) in [(*a, *b), (*b, *a)] {
+            let direction: &str = match maze.adjacent(from).iter().find(|(_, other)| *other == to) {
+                Some((direction, _)) => direction,
+                None => continue,
+            };
+            rooms[from].neighbors.push(Direction {
+                direction: direction.to_string(),
+                riddle_id: Some(riddle.id),
+                level: riddle.level,
+                counterpart: Option::default(),
+                door: DoorType::Regular,
+                target: Option::default(),
+                condition: Option::default(),
+                item: Option::default(),
+            });
+        }
+    }
+    for room in rooms.iter_mut() {
+        room.neighbors.sort_by_key(|neighbor| {
+            DIRECTIONS
+                .iter()
+                .position(|(direction, _, _)| *direction == neighbor.direction)
+        });
+    }
+    Ok(GameFile::from_parts(game, rooms, riddles))
+}
@@ -0,0 +1,58 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use std::collections::BTreeMap;
+
+/// A text in several languages, keyed by language tag, e.g. "de" or "en-US".
+pub type LocalizedText = BTreeMap<String, String>;
+
+/// Returns the language tags of an `Accept-Language` header, most preferred first.
+pub fn accepted_languages(header: &Option<String>) -> Vec<String> {
+    let header: &String = match header {
+        Some(header) => header,
+        None => return Vec::new(),
+    };
+    let mut languages: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut fields = part.split(';');
+            let tag: String = fields.next()?.trim().to_string();
+            if tag.is_empty() || tag == "*" {
+                return Option::default();
+            }
+            let quality: f32 = fields
+                .filter_map(|field| field.trim().strip_prefix("q="))
+                .filter_map(|q| q.parse().ok())
+                .next()
+                .unwrap_or(1.0);
+            Some((tag, quality))
+        })
+        .collect();
+    // The sort is stable, so tags with equal quality keep their order.
+    languages.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    languages.into_iter().map(|(tag, _)| tag).collect()
+}
+
+/// Picks the variant of `text` that best matches `languages`. A tag like
+/// "de-DE" also matches the plain language "de". If nothing matches, the
+/// first variant is returned.
+pub fn localize(text: &LocalizedText, languages: &Vec<String>) -> Option<String> {
+    for language in languages.iter() {
+        if let Some((_, value)) = text
+            .iter()
+            .find(|(tag, _)| tag.eq_ignore_ascii_case(language))
+        {
+            return Some(value.clone());
+        }
+        let primary: &str = language.split('-').next().unwrap_or(language);
+        if let Some((_, value)) = text.iter().find(|(tag, _)| {
+            tag.split('-')
+                .next()
+                .map_or(false, |tag| tag.eq_ignore_ascii_case(primary))
+        }) {
+            return Some(value.clone());
+        }
+    }
+    text.values().next().cloned()
+}
@@ -0,0 +1,387 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use bson::oid::ObjectId;
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use lazy_static::lazy_static;
+use log;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use warp::ws::{Message, WebSocket};
+use warp::Filter;
+
+const MAX_CHAT_MESSAGE_LENGTH: usize = 500;
+
+lazy_static! {
+    /// Number of chat messages a player may send within `CHAT_RATE_WINDOW` seconds.
+    static ref CHAT_RATE_LIMIT: usize = env::var("CHAT_RATE_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(5);
+    static ref CHAT_RATE_WINDOW: u64 = env::var("CHAT_RATE_WINDOW")
+        .ok()
+        .and_then(|window| window.parse().ok())
+        .unwrap_or(10);
+    /// Comma-separated list of words that are masked in chat messages.
+    static ref CHAT_BLOCKLIST: Vec<String> = env::var("CHAT_BLOCKLIST")
+        .unwrap_or_default()
+        .split(',')
+        .map(|word| word.trim().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect();
+}
+
+/// Events pushed to the players.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum Event {
+    /// Sent on connecting: the players in the same room.
+    Presence {
+        room_id: Option<ObjectId>,
+        usernames: Vec<String>,
+    },
+    Entered {
+        username: String,
+        room_id: ObjectId,
+    },
+    Left {
+        username: String,
+        room_id: ObjectId,
+    },
+    DoorSolved {
+        username: String,
+        room_id: ObjectId,
+        riddle_id: ObjectId,
+    },
+    Score {
+        username: String,
+        score: i32,
+    },
+    Chat {
+        username: String,
+        room_id: ObjectId,
+        text: String,
+        timestamp: i64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Messages sent by the players.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    Chat { text: String },
+}
+
+/// The outcome of a moderation hook.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// The message may be sent, possibly altered.
+    Accept(String),
+    Reject(String),
+}
+
+/// Checks a chat message of the player named in the first argument.
+pub type ModerationHook = fn(&str, &str) -> Verdict;
+
+fn limit_length(_username: &str, text: &str) -> Verdict {
+    match text.chars().count() {
+        0 => Verdict::Reject("message is empty".to_string()),
+        n if n > MAX_CHAT_MESSAGE_LENGTH => Verdict::Reject(format!(
+            "message is longer than {} characters",
+            MAX_CHAT_MESSAGE_LENGTH
+        )),
+        _ => Verdict::Accept(text.to_string()),
+    }
+}
+
+fn mask_blocked_words(_username: &str, text: &str) -> Verdict {
+    let masked: Vec<String> = text
+        .split(' ')
+        .map(|word| {
+            let normalized: String = word
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase();
+            match CHAT_BLOCKLIST.contains(&normalized) {
+                true => "*".repeat(word.chars().count()),
+                false => word.to_string(),
+            }
+        })
+        .collect();
+    Verdict::Accept(masked.join(" "))
+}
+
+struct Client {
+    username: String,
+    room_id: Option<ObjectId>,
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+/// Keeps track of the connected players and the rooms they are in.
+pub struct LiveHub {
+    next_id: usize,
+    clients: HashMap<usize, Client>,
+    chat_times: HashMap<String, VecDeque<Instant>>,
+    muted: HashSet<String>,
+    hooks: Vec<ModerationHook>,
+}
+
+pub type Hub = Arc<Mutex<LiveHub>>;
+
+impl LiveHub {
+    pub fn new() -> LiveHub {
+        LiveHub {
+            next_id: 0,
+            clients: HashMap::new(),
+            chat_times: HashMap::new(),
+            muted: HashSet::new(),
+            hooks: vec![limit_length, mask_blocked_words],
+        }
+    }
+
+    /// Hooks run in the order they were added; each one gets the message
+    /// as altered by the previous one.
+    pub fn add_moderation_hook(&mut self, hook: ModerationHook) {
+        self.hooks.push(hook);
+    }
+
+    pub fn mute(&mut self, username: &String) {
+        self.muted.insert(username.clone());
+    }
+
+    pub fn unmute(&mut self, username: &String) {
+        self.muted.remove(username);
+    }
+
+    fn send(client: &Client, event: &Event) {
+        let text: String = serde_json::to_string(event).unwrap();
+        // The receiving task has ended if sending fails; the client is
+        // removed when its connection closes.
+        let _ = client.sender.send(Message::text(text));
+    }
+
+    fn send_to_room(&self, room_id: &ObjectId, event: &Event) {
+        for client in self.clients.values() {
+            if client.room_id.as_ref() == Some(room_id) {
+                LiveHub::send(client, event);
+            }
+        }
+    }
+
+    fn send_to_all(&self, event: &Event) {
+        for client in self.clients.values() {
+            LiveHub::send(client, event);
+        }
+    }
+
+    fn usernames_in(&self, room_id: &Option<ObjectId>) -> Vec<String> {
+        let mut usernames: Vec<String> = self
+            .clients
+            .values()
+            .filter(|client| room_id.is_some() && &client.room_id == room_id)
+            .map(|client| client.username.clone())
+            .collect();
+        usernames.sort();
+        usernames.dedup();
+        usernames
+    }
+
+    fn connect(
+        &mut self,
+        username: &String,
+        room_id: Option<ObjectId>,
+        sender: mpsc::UnboundedSender<Message>,
+    ) -> usize {
+        let id: usize = self.next_id;
+        self.next_id += 1;
+        let client = Client {
+            username: username.clone(),
+            room_id,
+            sender,
+        };
+        LiveHub::send(
+            &client,
+            &Event::Presence {
+                room_id,
+                usernames: self.usernames_in(&room_id),
+            },
+        );
+        if let Some(room_id) = room_id {
+            self.send_to_room(
+                &room_id,
+                &Event::Entered {
+                    username: username.clone(),
+                    room_id,
+                },
+            );
+        }
+        self.clients.insert(id, client);
+        id
+    }
+
+    fn disconnect(&mut self, id: usize) {
+        if let Some(client) = self.clients.remove(&id) {
+            if let Some(room_id) = client.room_id {
+                self.send_to_room(
+                    &room_id,
+                    &Event::Left {
+                        username: client.username,
+                        room_id,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Tells the players in the room `username` left and in the room they
+    /// entered about the move.
+    pub fn moved(&mut self, username: &String, from: &ObjectId, to: &ObjectId) {
+        for client in self.clients.values_mut() {
+            if &client.username == username {
+                client.room_id = Some(*to);
+            }
+        }
+        self.send_to_room(
+            from,
+            &Event::Left {
+                username: username.clone(),
+                room_id: *from,
+            },
+        );
+        self.send_to_room(
+            to,
+            &Event::Entered {
+                username: username.clone(),
+                room_id: *to,
+            },
+        );
+    }
+
+    /// Tells the players in `rooms` that `username` has solved the riddle of a door.
+    pub fn door_solved(&self, username: &String, rooms: &Vec<ObjectId>, riddle_id: &ObjectId) {
+        for room_id in rooms.iter() {
+            self.send_to_room(
+                room_id,
+                &Event::DoorSolved {
+                    username: username.clone(),
+                    room_id: *room_id,
+                    riddle_id: *riddle_id,
+                },
+            );
+        }
+    }
+
+    pub fn score_changed(&self, username: &String, score: i32) {
+        self.send_to_all(&Event::Score {
+            username: username.clone(),
+            score,
+        });
+    }
+
+    fn chat(&mut self, id: usize, text: String) -> Result<(), String> {
+        let (username, room_id) = match self.clients.get(&id) {
+            Some(Client {
+                username,
+                room_id: Some(room_id),
+                ..
+            }) => (username.clone(), *room_id),
+            _ => return Err("you are in no room".to_string()),
+        };
+        if self.muted.contains(&username) {
+            return Err("you have been muted".to_string());
+        }
+        let now: Instant = Instant::now();
+        let window: Duration = Duration::from_secs(*CHAT_RATE_WINDOW);
+        // Players whose messages all lie outside the window are forgotten,
+        // so the map only holds those who have chatted recently.
+        self.chat_times.retain(|_, times| {
+            times
+                .back()
+                .map_or(false, |t| now.duration_since(*t) <= window)
+        });
+        let times: &mut VecDeque<Instant> = self.chat_times.entry(username.clone()).or_default();
+        while times
+            .front()
+            .map_or(false, |t| now.duration_since(*t) > window)
+        {
+            times.pop_front();
+        }
+        if times.len() >= *CHAT_RATE_LIMIT {
+            return Err("you are sending too many messages".to_string());
+        }
+        times.push_back(now);
+        let mut text: String = text.trim().to_string();
+        for hook in self.hooks.iter() {
+            match hook(&username, &text) {
+                Verdict::Accept(accepted) => text = accepted,
+                Verdict::Reject(reason) => return Err(reason),
+            }
+        }
+        self.send_to_room(
+            &room_id,
+            &Event::Chat {
+                username,
+                room_id,
+                text,
+                timestamp: Utc::now().timestamp(),
+            },
+        );
+        Ok(())
+    }
+}
+
+pub fn with_hub(hub: Hub) -> impl Filter<Extract = (Hub,), Error = Infallible> + Clone {
+    warp::any().map(move || hub.clone())
+}
+
+/// Serves the WebSocket connection of the player `username`, who is in
+/// the room `room_id`, until it is closed.
+pub async fn serve(socket: WebSocket, username: String, room_id: Option<ObjectId>, hub: Hub) {
+    log::info!("live connection of {} opened", &username);
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Message>();
+    tokio::task::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            if let Err(e) = ws_sender.send(message).await {
+                log::error!("{:?}", e);
+                break;
+            }
+        }
+    });
+    let id: usize = hub
+        .lock()
+        .unwrap()
+        .connect(&username, room_id, sender.clone());
+    while let Some(result) = ws_receiver.next().await {
+        let message: Message = match result {
+            Ok(message) => message,
+            Err(e) => {
+                log::error!("{:?}", e);
+                break;
+            }
+        };
+        let text: &str = match message.to_str() {
+            Ok(text) => text,
+            Err(()) => continue,
+        };
+        let outcome: Result<(), String> = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(ClientMessage::Chat { text }) => hub.lock().unwrap().chat(id, text),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(message) = outcome {
+            let event: String = serde_json::to_string(&Event::Error { message }).unwrap();
+            let _ = sender.send(Message::text(event));
+        }
+    }
+    hub.lock().unwrap().disconnect(id);
+    log::info!("live connection of {} closed", &username);
+}
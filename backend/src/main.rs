@@ -3,17 +3,22 @@
  * All rights reserved.
  */
 use crate::error::Error;
-use auth::{with_auth, Role};
+use auth::{with_auth, with_token_auth, Role};
 use base32;
 use bson::oid::ObjectId;
+use certificate::Certificate;
 use chrono::{serde::ts_seconds_option, DateTime, TimeZone, Utc};
 use db::{
-    with_db, Direction, PinType, Riddle, RiddleAttempt, Room, SecondFactor, User,
-    UserCompactScoreData, DB,
+    with_db, AuditAction, AuditEntry, Direction, DoorType, FinishedGame, Game, Item, Note, PinType,
+    PlacedItem, Riddle, RiddleAttempt, RiddleFeedback, RiddleRating, Room, RoomObject,
+    SecondFactor, Team, User, UserCompactScoreData, UserSolvedData, DB,
 };
 use dotenv::dotenv;
+use generator::{GeneratorOptions, Topology};
+use i18n::LocalizedText;
 use lazy_static::lazy_static;
 use lettre::{Message, SmtpTransport, Transport};
+use live::{with_hub, Hub, LiveHub};
 use log;
 use mongodb::bson::doc;
 use passwd::Password;
@@ -21,8 +26,11 @@ use qrcode_generator::QrCodeEcc;
 use rand::Rng;
 use rand_core::{OsRng, RngCore};
 use regex::Regex;
-use rlua;
-use scripting::{with_script_env, ScriptEnv, ScriptEnvMap};
+use script_lib::ScriptPlayer;
+use scripting::{
+    evaluate_script, new_seed, validate_script, with_script_env, Attachment, ScriptEnvMap,
+    ScriptEnvStats, ScriptResult,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
@@ -32,9 +40,10 @@ use std::fs;
 use std::io::{Read, Seek, SeekFrom};
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use totp_lite::{totp_custom, Sha1};
 use url_escape;
+use validator::ValidationIssue;
 use warp::{http::StatusCode, reject, reply::WithStatus, Filter, Rejection, Reply};
 use webauthn_rs::proto::{
     CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential,
@@ -42,11 +51,23 @@ use webauthn_rs::proto::{
 };
 
 mod auth;
+mod authoring;
 mod b64;
+mod certificate;
 mod db;
 mod error;
+mod game_file;
+mod generator;
+mod i18n;
+mod live;
+mod map;
+mod oid;
 mod passwd;
+mod route;
+mod script_lib;
 mod scripting;
+mod validator;
+mod wasm;
 mod webauthn;
 
 type Result<T> = std::result::Result<T, error::Error>;
@@ -70,13 +91,27 @@ lazy_static! {
         (String::from("e"), String::from("w")),
         (String::from("s"), String::from("n")),
         (String::from("w"), String::from("e")),
-        //(String::from("u"), String::from("d")),
-        //(String::from("d"), String::from("u")),
+        (String::from("u"), String::from("d")),
+        (String::from("d"), String::from("u")),
     ]);
     static ref RE_USERNAME: Regex = Regex::new(r"^\w+$").unwrap();
-    static ref RE_MAIL: Regex = Regex::new(r"^[a-zA-Z0-9_.+-]+@[a-zA-Z0-9-]+\.[a-zA-Z0-9-.]+$").unwrap();
+    static ref RE_MAIL: Regex =
+        Regex::new(r"^[a-zA-Z0-9_.+-]+@[a-zA-Z0-9-]+\.[a-zA-Z0-9-.]+$").unwrap();
+    /// Number of notes a player may leave within `NOTE_RATE_WINDOW` seconds.
+    static ref NOTE_RATE_LIMIT: u64 = env::var("NOTE_RATE_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(3);
+    static ref NOTE_RATE_WINDOW: i64 = env::var("NOTE_RATE_WINDOW")
+        .ok()
+        .and_then(|window| window.parse().ok())
+        .unwrap_or(3600);
 }
 
+const MAX_NOTE_LENGTH: usize = 280;
+const MAX_TEAM_NAME_LENGTH: usize = 40;
+const MAX_FEEDBACK_COMMENT_LENGTH: usize = 1000;
+
 #[repr(C)]
 union MD5Hash {
     hash: md5::Digest,
@@ -298,6 +333,23 @@ pub struct FileResponse {
     pub variants: Option<Vec<FileVariantResponse>>,
 }
 
+impl From<Attachment> for FileResponse {
+    fn from(attachment: Attachment) -> Self {
+        FileResponse {
+            ok: true,
+            message: Option::default(),
+            original_name: Some(attachment.name),
+            uploaded_name: Option::default(),
+            mime_type: attachment.mime_type,
+            data: attachment.data,
+            width: Option::default(),
+            height: Option::default(),
+            scale: Option::default(),
+            variants: Option::default(),
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct RiddleResponse {
     pub ok: bool,
@@ -328,6 +380,107 @@ pub struct RiddleSolvedResponse {
     pub level: u32,
     pub message: Option<String>,
     pub feedback: Option<String>,
+    pub items: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LookResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub number: u32,
+    pub coords: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "asciiArt")]
+    pub ascii_art: Option<String>,
+    pub objects: Vec<String>,
+    pub items: Vec<String>,
+    pub exits: Vec<String>,
+    pub notes: Vec<NoteResponse>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct FeedbackRequest {
+    pub difficulty: u8,
+    pub fun: u8,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RatingsResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub ratings: Vec<RiddleRating>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RiddleRatingsResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub rating: Option<RiddleRating>,
+    pub feedback: Vec<RiddleFeedback>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NoteRequest {
+    pub text: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NoteResponse {
+    pub id: ObjectId,
+    pub username: String,
+    pub text: String,
+    pub timestamp: i64,
+}
+
+impl From<Note> for NoteResponse {
+    fn from(note: Note) -> Self {
+        NoteResponse {
+            id: note.id,
+            username: note.username,
+            text: note.text,
+            timestamp: note.timestamp,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct NotesResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub notes: Vec<NoteResponse>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NoteAddedResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub id: ObjectId,
+    pub hidden: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportedNotesResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub notes: Vec<Note>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ExamineResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct InventoryResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub items: Vec<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -337,6 +490,31 @@ pub struct SteppedThroughResponse {
     pub room: RoomResponse,
 }
 
+#[derive(Serialize, Debug)]
+pub struct MapResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub game_id: ObjectId,
+    pub current: Option<ObjectId>,
+    pub rooms: Vec<map::MapRoom>,
+    pub ascii: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RouteResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub route: Vec<route::RouteStep>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TravelResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub route: Vec<route::RouteStep>,
+    pub room: RoomResponse,
+}
+
 #[derive(Serialize, Debug)]
 pub struct GameStatsResponse {
     pub ok: bool,
@@ -410,6 +588,55 @@ struct UserScoreResponse {
     pub rel_score: f32,
 }
 
+#[derive(Serialize, Debug)]
+pub struct TeamResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub id: ObjectId,
+    pub name: String,
+    pub members: Vec<String>,
+    pub invitations: Vec<String>,
+    pub level: u32,
+    pub score: i32,
+}
+
+impl From<Team> for TeamResponse {
+    fn from(team: Team) -> Self {
+        TeamResponse {
+            ok: true,
+            message: Option::default(),
+            id: team.id,
+            name: team.name,
+            members: team.members,
+            invitations: team.invitations,
+            level: team.level,
+            score: team.score,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct InvitationsResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub teams: Vec<TeamResponse>,
+}
+
+#[derive(Serialize, Debug)]
+struct TeamScoreResponse {
+    pub name: String,
+    pub members: Vec<String>,
+    pub level: u32,
+    pub score: i32,
+}
+
+#[derive(Serialize, Debug)]
+struct TeamHighscoresResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub highscores: Vec<TeamScoreResponse>,
+}
+
 #[derive(Serialize, Debug)]
 struct HighscoresResponse {
     pub ok: bool,
@@ -417,124 +644,85 @@ struct HighscoresResponse {
     pub highscores: Vec<UserScoreResponse>,
 }
 
-#[derive(Debug)]
-pub struct ScriptResult {
-    pub solution: Option<String>,
-    pub task: Option<String>,
-    pub feedback: Option<String>,
-    pub name: Option<String>,
-    pub mime_type: Option<String>,
+#[derive(Serialize, Debug)]
+struct ValidationResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub issues: Vec<ValidationIssue>,
 }
 
-pub fn evaluate_script(
-    username: &String,
-    script: &String,
-    guess: Option<String>,
-    env: Arc<Mutex<ScriptEnvMap>>,
-    load: bool,
-) -> ScriptResult {
-    dbg!(&guess);
-    let mut env = env.lock().unwrap();
-    if !env.contains_key(username) {
-        env.insert(username.clone(), ScriptEnv::new());
-        log::info!("inserted {} into script_env", username);
-    }
-    let env = env.get(username).unwrap();
-    log::info!("fetched {} from script_env", username);
-    let (solution, task, feedback, name, mime_type) = env.lua.context(|lua_ctx| {
-        if load {
-            match lua_ctx.load(&script).exec() {
-                Ok(()) => (),
-                Err(e) => {
-                    log::error!("{:?}", e);
-                    return (
-                        Option::default(),
-                        Option::default(),
-                        Option::default(),
-                        Option::default(),
-                        Option::default(),
-                    );
-                }
-            }
-        }
-        let globals = lua_ctx.globals();
-        let task: Option<String> = match globals.get::<_, rlua::Function>("task") {
-            Ok(f) => match f.call::<_, String>(()) {
-                Ok(result) => Some(result),
-                Err(e) => {
-                    log::error!("{:?}", e);
-                    Option::default()
-                }
-            },
-            Err(e) => {
-                log::error!("{:?}", e);
-                Option::default()
-            }
-        };
-        let mime_type: Option<String> = match globals.get::<_, rlua::Function>("mime_type") {
-            Ok(f) => match f.call::<_, String>(()) {
-                Ok(result) => Some(result),
-                Err(e) => {
-                    log::error!("{:?}", e);
-                    Option::default()
-                }
-            },
-            Err(e) => {
-                log::error!("{:?}", e);
-                Option::default()
-            }
-        };
-        let name: Option<String> = match globals.get::<_, rlua::Function>("name") {
-            Ok(f) => match f.call::<_, String>(()) {
-                Ok(result) => Some(result),
-                Err(e) => {
-                    log::error!("{:?}", e);
-                    Option::default()
-                }
-            },
-            Err(e) => {
-                log::error!("{:?}", e);
-                Option::default()
-            }
-        };
-        let solution: Option<String> = match globals.get::<_, rlua::Function>("solution") {
-            Ok(f) => match f.call::<_, String>(()) {
-                Ok(result) => Some(result),
-                Err(e) => {
-                    log::error!("{:?}", e);
-                    Option::default()
-                }
-            },
-            Err(e) => {
-                log::error!("{:?}", e);
-                Option::default()
-            }
-        };
-        let feedback: Option<String> = match guess {
-            Some(guess) => match globals.get::<_, rlua::Function>("try") {
-                Ok(f) => match f.call::<_, String>(guess) {
-                    Ok(result) => Some(result),
-                    Err(e) => {
-                        log::error!("{:?}", e);
-                        Option::default()
-                    }
-                },
-                Err(e) => {
-                    log::error!("{:?}", e);
-                    Option::default()
-                }
-            },
-            None => Option::default(),
-        };
-        (solution, task, feedback, name, mime_type)
-    });
-    ScriptResult {
-        solution,
-        task,
-        name,
-        mime_type,
-        feedback,
-    }
+#[derive(Serialize, Debug)]
+struct AuthoringResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub id: ObjectId,
+    pub version: u32,
+}
+
+#[derive(Serialize, Debug)]
+struct AuthoringGamesResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub games: Vec<Game>,
+}
+
+#[derive(Serialize, Debug)]
+struct AuthoringGameResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub game: Game,
+    pub rooms: Vec<Room>,
+}
+
+#[derive(Serialize, Debug)]
+struct AuthoringRoomResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub room: Room,
+}
+
+#[derive(Serialize, Debug)]
+struct AuthoringRiddleResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub riddle: Riddle,
+}
+
+#[derive(Serialize, Debug)]
+struct ScriptStatsResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub stats: ScriptEnvStats,
+}
+
+#[derive(Serialize, Debug)]
+struct AuditResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub entries: Vec<AuditEntry>,
+}
+
+#[derive(Serialize, Debug)]
+struct CertificateResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub certificate: Certificate,
+    pub signature: String,
+    #[serde(rename = "publicKey")]
+    pub public_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CertificateVerifyRequest {
+    pub certificate: Certificate,
+    pub signature: String,
+}
+
+#[derive(Serialize, Debug)]
+struct CertificateVerifyResponse {
+    pub ok: bool,
+    pub message: Option<String>,
+    pub valid: bool,
 }
 
 fn err_response(message: Option<String>) -> WithStatus<warp::reply::Json> {
@@ -573,7 +761,12 @@ pub async fn ping_handler() -> WebResult<impl Reply> {
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
 
-pub async fn go_handler(direction_str: String, username: String, db: DB) -> WebResult<impl Reply> {
+pub async fn go_handler(
+    direction_str: String,
+    username: String,
+    db: DB,
+    hub: Hub,
+) -> WebResult<impl Reply> {
     log::info!(
         "go_handler(); direction = {}; username = {}",
         &direction_str,
@@ -599,18 +792,37 @@ pub async fn go_handler(direction_str: String, username: String, db: DB) -> WebR
         Some(direction) => direction,
         None => return Err(reject::custom(Error::NeighborNotFoundError)),
     };
-    let riddle_id: bson::oid::ObjectId = match user
-        .solved
-        .iter()
-        .find(|&s| s.riddle_id == direction.riddle_id)
-    {
-        Some(riddle_attempt) => riddle_attempt.riddle_id,
-        None => return Err(reject::custom(Error::RiddleNotSolvedError)),
-    };
-    let opposite: &String = &OPPOSITE[&direction.direction];
-    let room_behind: Room = match db.get_room_behind(&opposite, &riddle_id).await {
-        Ok(room_behind) => room_behind,
-        Err(e) => return Err(reject::custom(e)),
+    if let Some(riddle_id) = &direction.riddle_id {
+        if !user.solved.iter().any(|s| &s.riddle_id == riddle_id) {
+            return Err(reject::custom(Error::RiddleNotSolvedError));
+        }
+    }
+    if let Some(condition) = &direction.condition {
+        if !condition.is_met_by(&user) {
+            return Err(reject::custom(Error::DoorLockedError));
+        }
+    }
+    if let Some(item) = &direction.item {
+        if !user.inventory.iter().any(|carried| carried.is_named(item)) {
+            return Err(reject::custom(Error::ItemRequiredError));
+        }
+    }
+    let room_behind: Room = match (&direction.door, &direction.target, &direction.riddle_id) {
+        (_, Some(target), _) => match db.get_room(target).await {
+            Ok(room_behind) => room_behind,
+            Err(e) => return Err(reject::custom(e)),
+        },
+        (DoorType::Regular, None, Some(riddle_id)) => {
+            let opposite: String = match direction.counterpart() {
+                Some(opposite) => opposite,
+                None => return Err(reject::custom(Error::RoomBehindNotFoundError)),
+            };
+            match db.get_room_behind(&room, &opposite, riddle_id).await {
+                Ok(room_behind) => room_behind,
+                Err(e) => return Err(reject::custom(e)),
+            }
+        }
+        _ => return Err(reject::custom(Error::RoomBehindNotFoundError)),
     };
     log::info!(
         "moving {} from {} to {}",
@@ -625,13 +837,13 @@ pub async fn go_handler(direction_str: String, username: String, db: DB) -> WebR
             "$set": {
                 "in_room": user.in_room,
             },
-            "$addToSet": { "rooms_entered": user.in_room },
             "$addToSet": {
+                "rooms_entered": user.in_room,
                 "finished": {
                     "game_id": room.game_id,
                     "timestamp": Utc::now().timestamp() as u32,
                 }
-            }
+            },
         },
         false => doc! {
             "$set": {
@@ -648,6 +860,9 @@ pub async fn go_handler(direction_str: String, username: String, db: DB) -> WebR
         Ok(_) => {}
         Err(e) => return Ok(err_response(Some(e.to_string()))),
     };
+    hub.lock()
+        .unwrap()
+        .moved(&username, &room.id, &room_behind.id);
     let in_room = match &user.in_room {
         Some(in_room) => in_room,
         None => return Err(reject::custom(Error::UserIsInNoRoom)),
@@ -674,480 +889,2241 @@ pub async fn go_handler(direction_str: String, username: String, db: DB) -> WebR
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
 
-pub async fn riddle_solve_handler(
-    riddle_id_str: OidString,
-    body: RiddleSolveRequest,
+/// Returns the user named `username` and the room they are in.
+async fn get_user_in_room(username: &String, db: &DB) -> Result<(User, Room)> {
+    let user: User = match db.get_user(username).await {
+        Ok(user) => user,
+        Err(e) => return Err(e),
+    };
+    let in_room: ObjectId = match user.in_room {
+        Some(in_room) => in_room,
+        None => return Err(Error::UserIsInNoRoom),
+    };
+    match db.get_room(&in_room).await {
+        Ok(room) => Ok((user, room)),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn room_look_handler(
+    accept_language: Option<String>,
     username: String,
-    mut db: DB,
-    script_env: Arc<Mutex<ScriptEnvMap>>,
+    db: DB,
 ) -> WebResult<impl Reply> {
-    let solution = url_escape::decode(&body.solution).into_owned();
-    log::info!(
-        "riddle_solve_handler(); riddle_id = {}, solution = {}",
-        &riddle_id_str,
-        &solution
-    );
-    let oid: bson::oid::ObjectId = match ObjectId::parse_str(riddle_id_str) {
-        Ok(oid) => oid,
-        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    log::info!("room_look_handler(); username = {}", &username);
+    let (user, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
     };
-    let (riddle_id, user, _msg) = db.riddle_accessibility(&oid, &username).await;
-    let riddle_id = match riddle_id {
-        Some(in_room) => in_room,
-        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    let languages: Vec<String> = i18n::accepted_languages(&accept_language);
+    let description: &LocalizedText = match (&room.solved_description, room.is_solved_by(&user)) {
+        (Some(solved_description), true) => solved_description,
+        _ => &room.description,
     };
-    let riddle: Option<Riddle> = match db.get_riddle_by_oid(&riddle_id).await {
-        Ok(riddle) => riddle,
+    let notes: Vec<Note> = match db.get_visible_notes(&room.id).await {
+        Ok(notes) => notes,
         Err(e) => return Err(reject::custom(e)),
     };
-    let riddle: Riddle = match riddle {
-        Some(riddle) => riddle,
-        None => return Err(reject::custom(Error::RiddleNotFoundError)),
-    };
-    let script_env_present = script_env.lock().unwrap().contains_key(&username);
-    let (calculated_solution, feedback) = match script_env_present && riddle.script.is_some() {
-        true => {
-            let result: ScriptResult = evaluate_script(
-                &username,
-                &riddle.script.unwrap(),
-                Some(solution.clone()),
-                script_env,
-                false,
-            );
-            (result.solution, result.feedback)
-        }
-        false => (Some(riddle.solution.clone()), Option::default()),
-    };
-    let solved: bool = match riddle.ignore_case.unwrap_or(false) {
-        true => calculated_solution.unwrap_or_default().to_lowercase() == solution.to_lowercase(),
-        false => calculated_solution.unwrap_or_default() == solution,
-    };
-    let mut user: User = match user {
-        Some(user) => user,
-        None => return Err(reject::custom(Error::UserNotFoundError)),
-    };
-    if solved {
-        let mut solutions: Vec<RiddleAttempt> = user.solved.clone();
-        let riddle_attempt = match user.current_riddle_attempt {
-            Some(ref riddle_attempt) => riddle_attempt,
-            None => return Err(reject::custom(Error::RiddleHasNotBeenSeenByUser)),
-        };
-        if riddle_attempt.t0.is_none() {
-            return Err(reject::custom(Error::RiddleHasNotBeenSeenByUser));
-        }
-        let t0 = riddle_attempt
-            .t0
-            .unwrap_or(Utc.ymd(1970, 1, 1).and_hms(0, 0, 0));
-        solutions.push(RiddleAttempt {
-            riddle_id: riddle.id,
-            t0: riddle_attempt.t0,
-            dt: Some(Utc::now().signed_duration_since(t0).num_seconds()),
-        });
-        user.level = riddle.level.max(user.level);
-        user.score += riddle.difficulty;
-        match db.set_user_solved(&solutions, &user).await {
-            Ok(()) => {
-                log::info!("User {} updated.", &username);
-            }
-            Err(e) => {
-                log::error!("Error: update failed: {}", &e);
-                return Err(reject::custom(Error::RiddleNotSolvedError));
-            }
-        }
-    } else {
-        let deduction = riddle.deduction.unwrap_or(0);
-        user.score = 0.max(user.score - deduction);
-        match db.rewrite_user_score(&user).await {
-            Ok(()) => {
-                log::info!("User updated.");
-            }
-            Err(e) => {
-                log::error!("Error: update failed: {}", &e);
-                return Err(reject::custom(Error::RiddleNotSolvedError));
-            }
-        }
-    }
-    let reply: warp::reply::Json = warp::reply::json(&json!(&RiddleSolvedResponse {
+    let reply: warp::reply::Json = warp::reply::json(&json!(&LookResponse {
         ok: true,
-        riddle_id: riddle.id,
-        solved,
-        score: user.score,
-        level: riddle.level,
         message: Option::default(),
-        feedback,
+        number: room.number,
+        coords: room.coords.clone(),
+        title: i18n::localize(&room.title, &languages),
+        description: i18n::localize(description, &languages),
+        ascii_art: room.ascii_art.clone(),
+        objects: room
+            .objects
+            .iter()
+            .map(|object| object.name.clone())
+            .collect(),
+        items: room
+            .items_visible_to(&user)
+            .iter()
+            .map(|item| item.name.clone())
+            .collect(),
+        exits: room
+            .neighbors
+            .iter()
+            .map(|neighbor| neighbor.direction.clone())
+            .collect(),
+        notes: notes.into_iter().map(NoteResponse::from).collect(),
     }));
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
 
-pub async fn riddle_get_oid_handler(
-    riddle_id_str: String,
+pub async fn room_examine_handler(
+    object_name: String,
+    accept_language: Option<String>,
     username: String,
     db: DB,
-    script_env: Arc<Mutex<ScriptEnvMap>>,
 ) -> WebResult<impl Reply> {
-    log::info!("riddle_get_oid_handler(); riddle_id = {}", &riddle_id_str);
-    let oid = match ObjectId::parse_str(riddle_id_str) {
-        Ok(oid) => oid,
-        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    let object_name = url_escape::decode(&object_name).into_owned();
+    log::info!(
+        "room_examine_handler(); object = {}, username = {}",
+        &object_name,
+        &username
+    );
+    let (user, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
     };
-    let (riddle_id, user, message) = db.riddle_accessibility(&oid, &username).await;
-    let riddle_id: bson::oid::ObjectId = match riddle_id {
-        Some(riddle_id) => riddle_id,
-        None => return Ok(err_response(message)),
+    let languages: Vec<String> = i18n::accepted_languages(&accept_language);
+    // Items in the room or in the inventory can be examined, too.
+    if let Some(item) = room
+        .items_visible_to(&user)
+        .iter()
+        .chain(user.inventory.iter())
+        .find(|item| item.is_named(&object_name))
+    {
+        let reply: warp::reply::Json = warp::reply::json(&json!(&ExamineResponse {
+            ok: true,
+            message: Option::default(),
+            name: item.name.clone(),
+            description: i18n::localize(&item.description, &languages),
+        }));
+        return Ok(warp::reply::with_status(reply, StatusCode::OK));
+    }
+    let object: &RoomObject = match room
+        .objects
+        .iter()
+        .find(|object| object.name.eq_ignore_ascii_case(&object_name))
+    {
+        Some(object) => object,
+        None => return Err(reject::custom(Error::ObjectNotFoundError)),
     };
-    let riddle: Option<Riddle> = match db.get_riddle_by_oid(&riddle_id).await {
-        Ok(riddle) => riddle,
-        Err(e) => return Err(reject::custom(e)),
+    let description: &LocalizedText = match (&object.solved_description, room.is_solved_by(&user)) {
+        (Some(solved_description), true) => solved_description,
+        _ => &object.description,
     };
-    let riddle: Riddle = match riddle {
-        Some(riddle) => riddle,
-        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    let reply: warp::reply::Json = warp::reply::json(&json!(&ExamineResponse {
+        ok: true,
+        message: Option::default(),
+        name: object.name.clone(),
+        description: i18n::localize(description, &languages),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn notes_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("notes_handler(); username = {}", &username);
+    let (_, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
     };
-    let mut user = match user {
-        Some(user) => user,
-        None => return Err(reject::custom(Error::UserNotAssociatedWithRiddle)),
+    let notes: Vec<Note> = match db.get_visible_notes(&room.id).await {
+        Ok(notes) => notes,
+        Err(e) => return Err(reject::custom(e)),
     };
-    let riddle_attempt = RiddleAttempt {
-        riddle_id,
-        t0: Some(Utc::now()),
-        dt: Option::default(),
+    let reply: warp::reply::Json = warp::reply::json(&json!(&NotesResponse {
+        ok: true,
+        message: Option::default(),
+        notes: notes.into_iter().map(NoteResponse::from).collect(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn note_add_handler(
+    body: NoteRequest,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!("note_add_handler(); username = {}", &username);
+    let text: String = body.text.trim().to_string();
+    if text.is_empty() || text.chars().count() > MAX_NOTE_LENGTH {
+        return Err(reject::custom(Error::NoteLengthError));
+    }
+    let (_, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
     };
-    user.current_riddle_attempt = Some(riddle_attempt);
+    let now: i64 = Utc::now().timestamp();
     match db
-        .get_users_coll()
-        .update_one(
-            doc! { "username": username.clone() },
-            doc! {
-                "$set": {
-                    "current_riddle_attempt": Some(bson::to_bson(&user.current_riddle_attempt).unwrap()),
-                },
-            },
-            None,
-        )
+        .count_notes_since(&username, now - *NOTE_RATE_WINDOW)
         .await
     {
-        Ok(_) => {
-            log::info!("Updated current_riddle_attempt of user '{}'.", &username);
-        }
-        Err(e) => {
-            log::error!("Error: update failed ({:?})", &e);
-            return Err(reject::custom(Error::MongoQueryError(e)));
+        Ok(count) if count >= *NOTE_RATE_LIMIT => {
+            return Err(reject::custom(Error::NoteRateLimitError))
         }
+        Ok(_) => (),
+        Err(e) => return Err(reject::custom(e)),
     }
-    let mut found_files: Vec<FileResponse> = Vec::new();
-    if let Some(ref script) = riddle.script {
-        let result: ScriptResult = evaluate_script(&username, script, None, script_env, true);
-        found_files.push(FileResponse {
-            ok: true,
-            message: Option::default(),
-            original_name: result.name,
-            uploaded_name: Option::default(),
-            mime_type: result.mime_type.unwrap_or_default(),
-            data: result.task.unwrap_or_default().as_bytes().to_vec(),
-            scale: Option::default(),
-            width: Option::default(),
-            height: Option::default(),
-            variants: Option::default(),
-        });
+    let room_id: ObjectId = room.id;
+    let riddle_ids: Vec<ObjectId> = db::riddle_ids_of_rooms(&vec![room]);
+    let riddles: Vec<Riddle> = match db.get_riddles_by_oids(&riddle_ids).await {
+        Ok(riddles) => riddles,
+        Err(e) => return Err(reject::custom(e)),
     };
-    if let Some(files) = riddle.files {
-        for file in files.iter() {
-            log::info!("trying to load file {:?}", &file);
-            let mut file_variants: Vec<FileVariantResponse> = Vec::new();
-            if let Some(variants) = &file.variants {
-                for variant in variants {
-                    file_variants.push(FileVariantResponse {
-                        original_name: variant.original_name.clone(),
-                        uploaded_name: variant.uploaded_name.clone(),
-                        scale: Some(variant.scale),
-                    });
-                }
-            }
-            found_files.push(FileResponse {
-                ok: true,
-                message: Option::default(),
-                original_name: Some(file.original_name.clone()),
-                uploaded_name: Some(file.uploaded_name.clone()),
-                mime_type: file.mime_type.clone(),
-                data: Vec::new(),
-                scale: file.scale,
-                width: file.width,
-                height: file.height,
-                variants: Some(file_variants),
-            })
-        }
+    // Notes giving away the solution of a door of the room are hidden
+    // right away, but kept for the admins to review.
+    let reveals_solution: bool = riddles.iter().any(|riddle| riddle.is_revealed_by(&text));
+    let note: Note = Note {
+        id: ObjectId::new(),
+        room_id,
+        username: username.clone(),
+        text,
+        timestamp: now,
+        hidden: reveals_solution,
+        reports: Vec::new(),
+    };
+    match db.add_note(&note).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
     }
-    let reply: warp::reply::Json = warp::reply::json(&json!(&RiddleResponse {
+    let reply: warp::reply::Json = warp::reply::json(&json!(&NoteAddedResponse {
         ok: true,
-        message: Option::default(),
-        id: riddle.id,
-        level: riddle.level,
-        difficulty: riddle.difficulty,
-        deduction: riddle.deduction.unwrap_or(0),
-        ignore_case: riddle.ignore_case.unwrap_or(false),
-        files: Option::from(found_files),
-        task: riddle.task,
-        credits: riddle.credits,
+        message: match reveals_solution {
+            true => Some("note gives away a solution and has been hidden".to_string()),
+            false => Option::default(),
+        },
+        id: note.id,
+        hidden: note.hidden,
     }));
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
 
-// This function is needed for manual debugging.
-pub async fn riddle_get_by_level_handler(
-    level: u32,
+pub async fn note_report_handler(
+    note_id_str: OidString,
     username: String,
     db: DB,
-    script_env: Arc<Mutex<ScriptEnvMap>>,
 ) -> WebResult<impl Reply> {
-    log::info!("riddle_get_by_level_handler(); level = {}", level);
-    let riddle: Option<Riddle> = match db.get_riddle_by_level(level).await {
-        Ok(riddle) => riddle,
-        Err(e) => return Err(reject::custom(e)),
-    };
-    let riddle: Riddle = match riddle {
-        Some(riddle) => riddle,
-        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    log::info!(
+        "note_report_handler(); note_id = {}, username = {}",
+        &note_id_str,
+        &username
+    );
+    let note_id: ObjectId = match ObjectId::parse_str(note_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
     };
-    log::info!("got riddle w/ level = {}", riddle.level);
-    let mut found_files: Vec<FileResponse> = Vec::new();
-    if let Some(files) = riddle.files {
-        for file in files.iter() {
-            log::info!("trying to load file {:?}", &file);
-            let mut file_variants: Vec<FileVariantResponse> = Vec::new();
-            if let Some(variants) = &file.variants {
-                for variant in variants {
-                    file_variants.push(FileVariantResponse {
-                        original_name: variant.original_name.clone(),
-                        uploaded_name: variant.uploaded_name.clone(),
-                        scale: Some(variant.scale),
-                    });
-                }
-            }
-            found_files.push(FileResponse {
-                ok: true,
-                message: Option::default(),
-                original_name: Some(file.original_name.clone()),
-                uploaded_name: Some(file.uploaded_name.clone()),
-                mime_type: file.mime_type.clone(),
-                data: Vec::new(),
-                scale: file.scale,
-                width: file.width,
-                height: file.height,
-                variants: Some(file_variants),
-            })
-        }
+    match db.report_note(&note_id, &username).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
     }
-    let _scripted_solution: Option<String> = match riddle.script {
-        Some(ref script) => {
-            let mut env = script_env.lock().unwrap();
-            if !env.contains_key(&username) {
-                env.insert(username.clone(), ScriptEnv::new());
-                log::debug!("inserted {} into script_env", &username);
-            }
-            let env = env.get(&username).unwrap();
-            log::debug!("fetched {} from script_env", &username);
-            let solution: Option<String> = env.lua.context(|lua_ctx| {
-                match lua_ctx.load(&script).exec() {
-                    Ok(()) => (),
-                    Err(e) => {
-                        log::error!("{:?}", e);
-                        return Option::default();
-                    }
-                }
-                let globals = lua_ctx.globals();
-                let task: Option<String> = match globals.get::<_, rlua::Function>("task") {
-                    Ok(f) => match f.call::<_, String>(()) {
-                        Ok(result) => Some(result),
-                        Err(e) => {
-                            log::error!("{:?}", e);
-                            Option::default()
-                        }
-                    },
-                    Err(e) => {
-                        log::error!("{:?}", e);
-                        Option::default()
-                    }
-                };
-                let mime_type: Option<String> = match globals.get::<_, rlua::Function>("mime_type")
-                {
-                    Ok(f) => match f.call::<_, String>(()) {
-                        Ok(result) => Some(result),
-                        Err(e) => {
-                            log::error!("{:?}", e);
-                            Option::default()
-                        }
-                    },
-                    Err(e) => {
-                        log::error!("{:?}", e);
-                        Option::default()
-                    }
-                };
-                let script_name: Option<String> = match globals.get::<_, rlua::Function>("name") {
-                    Ok(f) => match f.call::<_, String>(()) {
-                        Ok(result) => Some(result),
-                        Err(e) => {
-                            log::error!("{:?}", e);
-                            Option::default()
-                        }
-                    },
-                    Err(e) => {
-                        log::error!("{:?}", e);
-                        Option::default()
-                    }
-                };
-                found_files.push(FileResponse {
-                    ok: true,
-                    message: Option::default(),
-                    original_name: script_name,
-                    uploaded_name: Option::default(),
-                    mime_type: mime_type.unwrap_or_default(),
-                    data: task.clone().unwrap_or_default().as_bytes().to_vec(),
-                    width: Option::default(),
-                    height: Option::default(),
-                    scale: Option::default(),
-                    variants: Option::default(),
-                });
-                let solution: Option<String> = match globals.get::<_, rlua::Function>("solution") {
-                    Ok(f) => match f.call::<_, String>(()) {
-                        Ok(result) => Some(result),
-                        Err(e) => {
-                            log::error!("{:?}", e);
-                            Option::default()
-                        }
-                    },
-                    Err(e) => {
-                        log::error!("{:?}", e);
-                        Option::default()
-                    }
-                };
-                solution
-            });
-            solution
-        }
-        None => Option::default(),
+    let reply: warp::reply::Json = warp::reply::json(&json!(&StatusResponse {
+        ok: true,
+        message: Option::default(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn reported_notes_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("reported_notes_handler(); username = {}", &username);
+    let notes: Vec<Note> = match db.get_reported_notes().await {
+        Ok(notes) => notes,
+        Err(e) => return Err(reject::custom(e)),
     };
-    let reply: warp::reply::Json = warp::reply::json(&json!(&RiddleResponse {
+    let reply: warp::reply::Json = warp::reply::json(&json!(&ReportedNotesResponse {
         ok: true,
         message: Option::default(),
-        id: riddle.id,
-        level: riddle.level,
-        difficulty: riddle.difficulty,
-        deduction: riddle.deduction.unwrap_or(0),
-        ignore_case: riddle.ignore_case.unwrap_or(false),
-        files: Option::from(found_files),
-        task: riddle.task,
-        credits: riddle.credits,
+        notes,
     }));
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
 
-pub async fn debriefing_get_by_riddle_id_handler(
-    riddle_id_str: String,
+pub async fn note_hide_handler(
+    note_id_str: OidString,
+    hidden: bool,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "note_hide_handler(); note_id = {}, hidden = {}, username = {}",
+        &note_id_str,
+        hidden,
+        &username
+    );
+    let note_id: ObjectId = match ObjectId::parse_str(note_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    match db.set_note_hidden(&note_id, hidden).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&StatusResponse {
+        ok: true,
+        message: Option::default(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn travel_handler(
+    room_id_str: OidString,
+    username: String,
+    db: DB,
+    hub: Hub,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "travel_handler(); room_id = {}, username = {}",
+        &room_id_str,
+        &username
+    );
+    let room_id: bson::oid::ObjectId = match ObjectId::parse_str(room_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let (mut user, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    if !user.rooms_entered.contains(&room_id) {
+        return Err(reject::custom(Error::RoomNotVisitedError));
+    }
+    let rooms: Vec<Room> = match db.get_rooms_of_game(&room.game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let route: Vec<route::RouteStep> =
+        match route::find_route(&rooms, &user, &room.id, |other| other.id == room_id) {
+            Some(route) => route,
+            None => return Err(reject::custom(Error::RouteNotFoundError)),
+        };
+    log::info!(
+        "moving {} from {} to {} in {} steps",
+        &username,
+        &room.id,
+        &room_id,
+        route.len()
+    );
+    user.in_room = Some(room_id);
+    match db.set_user_in_room(&user).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    hub.lock().unwrap().moved(&username, &room.id, &room_id);
+    let room: RoomResponse = match get_room_by_id(&room_id, &db).await {
+        Ok(room) => room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&TravelResponse {
+        ok: true,
+        message: Option::default(),
+        route,
+        room,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn route_entrance_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("route_entrance_handler(); username = {}", &username);
+    let (user, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let rooms: Vec<Room> = match db.get_rooms_of_game(&room.game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let route: Vec<route::RouteStep> = match route::find_route(&rooms, &user, &room.id, |other| {
+        other.entry.unwrap_or(false)
+    }) {
+        Some(route) => route,
+        None => return Err(reject::custom(Error::RouteNotFoundError)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&RouteResponse {
+        ok: true,
+        message: Option::default(),
+        route,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn live_handler(
+    ws: warp::ws::Ws,
+    username: String,
+    db: DB,
+    hub: Hub,
+) -> WebResult<impl Reply> {
+    log::info!("live_handler(); username = {}", &username);
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    Ok(ws.on_upgrade(move |socket| live::serve(socket, username, user.in_room, hub)))
+}
+
+pub async fn chat_mute_handler(
+    muted_username: String,
+    mute: bool,
+    username: String,
+    hub: Hub,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "chat_mute_handler(); muted_username = {}, mute = {}, username = {}",
+        &muted_username,
+        mute,
+        &username
+    );
+    match mute {
+        true => hub.lock().unwrap().mute(&muted_username),
+        false => hub.lock().unwrap().unmute(&muted_username),
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&StatusResponse {
+        ok: true,
+        message: Option::default(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn inventory_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("inventory_handler(); username = {}", &username);
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&InventoryResponse {
+        ok: true,
+        message: Option::default(),
+        items: user
+            .inventory
+            .iter()
+            .map(|item| item.name.clone())
+            .collect(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn item_take_handler(
+    item_name: String,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    let item_name = url_escape::decode(&item_name).into_owned();
+    log::info!(
+        "item_take_handler(); item = {}, username = {}",
+        &item_name,
+        &username
+    );
+    let (mut user, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let item: Item = match room
+        .items_visible_to(&user)
+        .into_iter()
+        .find(|item| item.is_named(&item_name))
+    {
+        Some(item) => item,
+        None => return Err(reject::custom(Error::ItemNotFoundError)),
+    };
+    // Picking up an item dropped before takes precedence over the original.
+    let placed: PlacedItem = PlacedItem {
+        item: item.clone(),
+        room_id: room.id,
+    };
+    match user
+        .items_dropped
+        .iter()
+        .position(|dropped| dropped == &placed)
+    {
+        Some(idx) => {
+            user.items_dropped.remove(idx);
+        }
+        None => user.items_taken.push(placed),
+    }
+    user.inventory.push(item);
+    match db.set_user_items(&user).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&InventoryResponse {
+        ok: true,
+        message: Option::default(),
+        items: user
+            .inventory
+            .iter()
+            .map(|item| item.name.clone())
+            .collect(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn item_drop_handler(
+    item_name: String,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    let item_name = url_escape::decode(&item_name).into_owned();
+    log::info!(
+        "item_drop_handler(); item = {}, username = {}",
+        &item_name,
+        &username
+    );
+    let (mut user, room) = match get_user_in_room(&username, &db).await {
+        Ok(user_and_room) => user_and_room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let item: Item = match user
+        .inventory
+        .iter()
+        .position(|item| item.is_named(&item_name))
+    {
+        Some(idx) => user.inventory.remove(idx),
+        None => return Err(reject::custom(Error::ItemNotFoundError)),
+    };
+    user.items_dropped.push(PlacedItem {
+        item,
+        room_id: room.id,
+    });
+    match db.set_user_items(&user).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&InventoryResponse {
+        ok: true,
+        message: Option::default(),
+        items: user
+            .inventory
+            .iter()
+            .map(|item| item.name.clone())
+            .collect(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn riddle_solve_handler(
+    riddle_id_str: OidString,
+    body: RiddleSolveRequest,
+    accept_language: Option<String>,
+    username: String,
+    mut db: DB,
+    script_env: Arc<Mutex<ScriptEnvMap>>,
+    hub: Hub,
+) -> WebResult<impl Reply> {
+    let solution = url_escape::decode(&body.solution).into_owned();
+    log::info!(
+        "riddle_solve_handler(); riddle_id = {}, solution = {}",
+        &riddle_id_str,
+        &solution
+    );
+    let oid: bson::oid::ObjectId = match ObjectId::parse_str(riddle_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let (riddle_id, user, _msg) = db.riddle_accessibility(&oid, &username).await;
+    let riddle_id = match riddle_id {
+        Some(in_room) => in_room,
+        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    };
+    let riddle: Option<Riddle> = match db.get_riddle_by_oid(&riddle_id).await {
+        Ok(riddle) => riddle,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let riddle: Riddle = match riddle {
+        Some(riddle) => riddle,
+        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    };
+    let (calculated_solution, verdict, feedback, granted_items) = match riddle.script {
+        Some(ref script) => {
+            // The task shown to the player is regenerated from the seed of
+            // their attempt, should the script environment be gone.
+            let seed: i64 = match user.as_ref().and_then(|user| user.seed_for(&riddle.id)) {
+                Some(seed) => seed,
+                None => return Err(reject::custom(Error::RiddleHasNotBeenSeenByUser)),
+            };
+            let level: u32 = user.as_ref().map_or(0, |user| user.level);
+            let player: ScriptPlayer = ScriptPlayer::new(&username, level, &accept_language);
+            let result: ScriptResult = match evaluate_script(
+                &player,
+                &riddle.id,
+                seed,
+                riddle.script_engine.unwrap_or_default(),
+                script,
+                Some(solution.clone()),
+                script_env,
+            )
+            .await
+            {
+                Ok(result) => result,
+                Err(e) => return Err(reject::custom(e)),
+            };
+            (
+                result.solution,
+                result.verdict,
+                result.feedback,
+                result.items,
+            )
+        }
+        None => (
+            Some(riddle.solution.clone()),
+            Option::default(),
+            Option::default(),
+            Vec::new(),
+        ),
+    };
+    // A script's own verification takes precedence over comparing strings.
+    let solved: bool = match verdict {
+        Some(correct) => correct,
+        None => {
+            riddle.normalize(&calculated_solution.unwrap_or_default())
+                == riddle.normalize(&solution)
+        }
+    };
+    let mut user: User = match user {
+        Some(user) => user,
+        None => return Err(reject::custom(Error::UserNotFoundError)),
+    };
+    let mut score_changed: bool = true;
+    if solved {
+        let riddle_attempt = match user.current_riddle_attempt {
+            Some(ref riddle_attempt) => riddle_attempt,
+            None => return Err(reject::custom(Error::RiddleHasNotBeenSeenByUser)),
+        };
+        if riddle_attempt.t0.is_none() {
+            return Err(reject::custom(Error::RiddleHasNotBeenSeenByUser));
+        }
+        let t0 = riddle_attempt
+            .t0
+            .unwrap_or(Utc.ymd(1970, 1, 1).and_hms(0, 0, 0));
+        let attempt: RiddleAttempt = RiddleAttempt {
+            riddle_id: riddle.id,
+            t0: riddle_attempt.t0,
+            dt: Some(Utc::now().signed_duration_since(t0).num_seconds()),
+            seed: user.seed_for(&riddle.id),
+        };
+        // A riddle solved before, by the player or a teammate, is not
+        // credited again.
+        score_changed = match db
+            .set_user_solved(&attempt, riddle.difficulty, riddle.level, &user)
+            .await
+        {
+            Ok(credited) => {
+                log::info!("User {} updated.", &username);
+                credited
+            }
+            Err(e) => {
+                log::error!("Error: update failed: {}", &e);
+                return Err(reject::custom(Error::RiddleNotSolvedError));
+            }
+        };
+        if score_changed {
+            user.level = riddle.level.max(user.level);
+            user.score += riddle.difficulty;
+        }
+        if let Some(team_id) = &user.team_id {
+            let team: Team = match db.get_team(team_id).await {
+                Ok(team) => team,
+                Err(e) => return Err(reject::custom(e)),
+            };
+            match db
+                .set_team_solved(&team, &attempt, riddle.difficulty, riddle.level)
+                .await
+            {
+                Ok(()) => (),
+                Err(e) => return Err(reject::custom(e)),
+            }
+        }
+        for name in granted_items.iter() {
+            if !user.inventory.iter().any(|item| item.is_named(name)) {
+                user.inventory.push(Item::new(name));
+            }
+        }
+        if !granted_items.is_empty() {
+            match db.set_user_items(&user).await {
+                Ok(()) => (),
+                Err(e) => return Err(reject::custom(e)),
+            }
+        }
+        let rooms: Vec<ObjectId> = match db.get_rooms_by_riddle(&riddle.id).await {
+            Ok(rooms) => rooms.iter().map(|room| room.id).collect(),
+            Err(e) => return Err(reject::custom(e)),
+        };
+        hub.lock()
+            .unwrap()
+            .door_solved(&username, &rooms, &riddle.id);
+    } else {
+        let deduction = riddle.deduction.unwrap_or(0);
+        user.score = 0.max(user.score - deduction);
+        match db.rewrite_user_score(&user).await {
+            Ok(()) => {
+                log::info!("User updated.");
+            }
+            Err(e) => {
+                log::error!("Error: update failed: {}", &e);
+                return Err(reject::custom(Error::RiddleNotSolvedError));
+            }
+        }
+        if let Some(team_id) = &user.team_id {
+            match db.deduct_team_score(team_id, deduction).await {
+                Ok(()) => (),
+                Err(e) => return Err(reject::custom(e)),
+            }
+        }
+    }
+    if score_changed {
+        hub.lock().unwrap().score_changed(&username, user.score);
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&RiddleSolvedResponse {
+        ok: true,
+        riddle_id: riddle.id,
+        solved,
+        score: user.score,
+        level: riddle.level,
+        message: Option::default(),
+        feedback,
+        items: match solved {
+            true => granted_items,
+            false => Vec::new(),
+        },
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn riddle_get_oid_handler(
+    riddle_id_str: String,
+    accept_language: Option<String>,
+    username: String,
+    db: DB,
+    script_env: Arc<Mutex<ScriptEnvMap>>,
+) -> WebResult<impl Reply> {
+    log::info!("riddle_get_oid_handler(); riddle_id = {}", &riddle_id_str);
+    let oid = match ObjectId::parse_str(riddle_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let (riddle_id, user, message) = db.riddle_accessibility(&oid, &username).await;
+    let riddle_id: bson::oid::ObjectId = match riddle_id {
+        Some(riddle_id) => riddle_id,
+        None => return Ok(err_response(message)),
+    };
+    let riddle: Option<Riddle> = match db.get_riddle_by_oid(&riddle_id).await {
+        Ok(riddle) => riddle,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let riddle: Riddle = match riddle {
+        Some(riddle) => riddle,
+        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    };
+    let mut user = match user {
+        Some(user) => user,
+        None => return Err(reject::custom(Error::UserNotAssociatedWithRiddle)),
+    };
+    // Opening the riddle again shows the same task, even after the player
+    // has opened other riddles in between.
+    let seed: Option<i64> = match riddle.script {
+        Some(_) => match user.seed_for(&riddle_id) {
+            Some(seed) => Some(seed),
+            None => match db.add_riddle_seed(&username, &riddle_id, new_seed()).await {
+                Ok(seed) => Some(seed),
+                Err(e) => return Err(reject::custom(e)),
+            },
+        },
+        None => Option::default(),
+    };
+    let riddle_attempt = RiddleAttempt {
+        riddle_id,
+        t0: Some(Utc::now()),
+        dt: Option::default(),
+        seed,
+    };
+    user.current_riddle_attempt = Some(riddle_attempt);
+    match db
+        .get_users_coll()
+        .update_one(
+            doc! { "username": username.clone() },
+            doc! {
+                "$set": {
+                    "current_riddle_attempt": Some(bson::to_bson(&user.current_riddle_attempt).unwrap()),
+                },
+            },
+            None,
+        )
+        .await
+    {
+        Ok(_) => {
+            log::info!("Updated current_riddle_attempt of user '{}'.", &username);
+        }
+        Err(e) => {
+            log::error!("Error: update failed ({:?})", &e);
+            return Err(reject::custom(Error::MongoQueryError(e)));
+        }
+    }
+    let mut found_files: Vec<FileResponse> = Vec::new();
+    if let Some(ref script) = riddle.script {
+        let player: ScriptPlayer = ScriptPlayer::new(&username, user.level, &accept_language);
+        let result: ScriptResult = match evaluate_script(
+            &player,
+            &riddle.id,
+            seed.unwrap_or_default(),
+            riddle.script_engine.unwrap_or_default(),
+            script,
+            None,
+            script_env,
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(reject::custom(e)),
+        };
+        found_files.push(FileResponse {
+            ok: true,
+            message: Option::default(),
+            original_name: result.name,
+            uploaded_name: Option::default(),
+            mime_type: result.mime_type.unwrap_or_default(),
+            data: result.task.unwrap_or_default(),
+            scale: Option::default(),
+            width: Option::default(),
+            height: Option::default(),
+            variants: Option::default(),
+        });
+        found_files.extend(result.attachments.into_iter().map(FileResponse::from));
+    };
+    if let Some(files) = riddle.files {
+        for file in files.iter() {
+            log::info!("trying to load file {:?}", &file);
+            let mut file_variants: Vec<FileVariantResponse> = Vec::new();
+            if let Some(variants) = &file.variants {
+                for variant in variants {
+                    file_variants.push(FileVariantResponse {
+                        original_name: variant.original_name.clone(),
+                        uploaded_name: variant.uploaded_name.clone(),
+                        scale: Some(variant.scale),
+                    });
+                }
+            }
+            found_files.push(FileResponse {
+                ok: true,
+                message: Option::default(),
+                original_name: Some(file.original_name.clone()),
+                uploaded_name: Some(file.uploaded_name.clone()),
+                mime_type: file.mime_type.clone(),
+                data: Vec::new(),
+                scale: file.scale,
+                width: file.width,
+                height: file.height,
+                variants: Some(file_variants),
+            })
+        }
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&RiddleResponse {
+        ok: true,
+        message: Option::default(),
+        id: riddle.id,
+        level: riddle.level,
+        difficulty: riddle.difficulty,
+        deduction: riddle.deduction.unwrap_or(0),
+        ignore_case: riddle.ignore_case.unwrap_or(false),
+        files: Option::from(found_files),
+        task: riddle.task,
+        credits: riddle.credits,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+/// Serves the attachment with the index `idx` the riddle's script generated
+/// for the player's current attempt as a file.
+pub async fn riddle_attachment_handler(
+    riddle_id_str: OidString,
+    idx: usize,
+    accept_language: Option<String>,
+    username: String,
+    db: DB,
+    script_env: Arc<Mutex<ScriptEnvMap>>,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "riddle_attachment_handler(); riddle_id = {}, idx = {}, username = {}",
+        &riddle_id_str,
+        idx,
+        &username
+    );
+    let oid: ObjectId = match ObjectId::parse_str(riddle_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let (riddle_id, user, _msg) = db.riddle_accessibility(&oid, &username).await;
+    let riddle_id: ObjectId = match riddle_id {
+        Some(riddle_id) => riddle_id,
+        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    };
+    let user: User = match user {
+        Some(user) => user,
+        None => return Err(reject::custom(Error::UserNotFoundError)),
+    };
+    let riddle: Riddle = match db.get_riddle_by_oid(&riddle_id).await {
+        Ok(Some(riddle)) => riddle,
+        Ok(None) => return Err(reject::custom(Error::RiddleNotFoundError)),
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let script: &String = match riddle.script {
+        Some(ref script) => script,
+        None => return Err(reject::custom(Error::AttachmentNotFoundError)),
+    };
+    let seed: i64 = match user.seed_for(&riddle.id) {
+        Some(seed) => seed,
+        None => return Err(reject::custom(Error::RiddleHasNotBeenSeenByUser)),
+    };
+    let player: ScriptPlayer = ScriptPlayer::new(&username, user.level, &accept_language);
+    let result: ScriptResult = match evaluate_script(
+        &player,
+        &riddle.id,
+        seed,
+        riddle.script_engine.unwrap_or_default(),
+        script,
+        None,
+        script_env,
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let attachment: Attachment = match result.attachments.into_iter().nth(idx) {
+        Some(attachment) => attachment,
+        None => return Err(reject::custom(Error::AttachmentNotFoundError)),
+    };
+    let disposition: String = format!(
+        "attachment; filename=\"{}\"",
+        attachment.name.replace(
+            |c: char| c == '"' || c == '\\' || !c.is_ascii() || c.is_ascii_control(),
+            "_"
+        )
+    );
+    Ok(warp::reply::with_header(
+        warp::reply::with_header(attachment.data, "Content-Type", attachment.mime_type),
+        "Content-Disposition",
+        disposition,
+    ))
+}
+
+// This function is needed for manual debugging.
+pub async fn riddle_get_by_level_handler(
+    level: u32,
+    accept_language: Option<String>,
+    username: String,
+    db: DB,
+    script_env: Arc<Mutex<ScriptEnvMap>>,
+) -> WebResult<impl Reply> {
+    log::info!("riddle_get_by_level_handler(); level = {}", level);
+    let riddle: Option<Riddle> = match db.get_riddle_by_level(level).await {
+        Ok(riddle) => riddle,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let riddle: Riddle = match riddle {
+        Some(riddle) => riddle,
+        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    };
+    log::info!("got riddle w/ level = {}", riddle.level);
+    let mut found_files: Vec<FileResponse> = Vec::new();
+    if let Some(files) = riddle.files {
+        for file in files.iter() {
+            log::info!("trying to load file {:?}", &file);
+            let mut file_variants: Vec<FileVariantResponse> = Vec::new();
+            if let Some(variants) = &file.variants {
+                for variant in variants {
+                    file_variants.push(FileVariantResponse {
+                        original_name: variant.original_name.clone(),
+                        uploaded_name: variant.uploaded_name.clone(),
+                        scale: Some(variant.scale),
+                    });
+                }
+            }
+            found_files.push(FileResponse {
+                ok: true,
+                message: Option::default(),
+                original_name: Some(file.original_name.clone()),
+                uploaded_name: Some(file.uploaded_name.clone()),
+                mime_type: file.mime_type.clone(),
+                data: Vec::new(),
+                scale: file.scale,
+                width: file.width,
+                height: file.height,
+                variants: Some(file_variants),
+            })
+        }
+    }
+    if let Some(ref script) = riddle.script {
+        // The script sees the admin as a player about to solve the riddle.
+        let player: ScriptPlayer =
+            ScriptPlayer::new(&username, level.saturating_sub(1), &accept_language);
+        let result: ScriptResult = match evaluate_script(
+            &player,
+            &riddle.id,
+            new_seed(),
+            riddle.script_engine.unwrap_or_default(),
+            script,
+            None,
+            script_env,
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(reject::custom(e)),
+        };
+        found_files.push(FileResponse {
+            ok: true,
+            message: Option::default(),
+            original_name: result.name,
+            uploaded_name: Option::default(),
+            mime_type: result.mime_type.unwrap_or_default(),
+            data: result.task.unwrap_or_default(),
+            width: Option::default(),
+            height: Option::default(),
+            scale: Option::default(),
+            variants: Option::default(),
+        });
+        found_files.extend(result.attachments.into_iter().map(FileResponse::from));
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&RiddleResponse {
+        ok: true,
+        message: Option::default(),
+        id: riddle.id,
+        level: riddle.level,
+        difficulty: riddle.difficulty,
+        deduction: riddle.deduction.unwrap_or(0),
+        ignore_case: riddle.ignore_case.unwrap_or(false),
+        files: Option::from(found_files),
+        task: riddle.task,
+        credits: riddle.credits,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn debriefing_get_by_riddle_id_handler(
+    riddle_id_str: String,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "debriefing_get_by_riddle_id_handler(); riddle_id = {}, username = {}",
+        &riddle_id_str,
+        &username
+    );
+    let oid: bson::oid::ObjectId = match ObjectId::parse_str(riddle_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let solved_riddle: Option<Riddle> = match db.get_riddle_if_solved(&oid, &username, None).await {
+        Ok(riddle) => riddle,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let riddle: Riddle = match solved_riddle {
+        Some(riddle) => riddle,
+        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    };
+    log::info!("got riddle {}", riddle.level);
+    let reply: warp::reply::Json = warp::reply::json(&json!(&DebriefingResponse {
+        ok: true,
+        message: Option::default(),
+        debriefing: riddle.debriefing,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn riddle_feedback_handler(
+    riddle_id_str: OidString,
+    body: FeedbackRequest,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "riddle_feedback_handler(); riddle_id = {}, username = {}",
+        &riddle_id_str,
+        &username
+    );
+    let riddle_id: ObjectId = match ObjectId::parse_str(riddle_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    if !(1..=5).contains(&body.difficulty) || !(1..=5).contains(&body.fun) {
+        return Err(reject::custom(Error::InvalidRatingError));
+    }
+    let comment: Option<String> = body
+        .comment
+        .map(|comment| comment.trim().to_string())
+        .filter(|comment| !comment.is_empty());
+    if let Some(comment) = &comment {
+        if comment.chars().count() > MAX_FEEDBACK_COMMENT_LENGTH {
+            return Err(reject::custom(Error::InvalidRatingError));
+        }
+    }
+    match db.get_riddle_if_solved(&riddle_id, &username, None).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(reject::custom(Error::RiddleNotSolvedError)),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let feedback: RiddleFeedback = RiddleFeedback {
+        id: ObjectId::new(),
+        riddle_id,
+        username,
+        difficulty: body.difficulty,
+        fun: body.fun,
+        comment,
+        timestamp: Utc::now().timestamp(),
+    };
+    match db.set_riddle_feedback(&feedback).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&StatusResponse {
+        ok: true,
+        message: Option::default(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn game_stats_handler(
+    game_id_str: String,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "game_stats_handler(); game_id = {}, username = {}",
+        &game_id_str,
+        &username
+    );
+    let game_id: bson::oid::ObjectId = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let num_rooms: u32 = match db.get_num_rooms(&game_id).await {
+        Ok(num_rooms) => num_rooms,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let num_riddles: u32 = match db.get_num_riddles(&game_id).await {
+        Ok(num_riddles) => num_riddles,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let max_score: u32 = match db.get_max_score_for_game(&game_id).await {
+        Ok(max_score) => max_score,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&GameStatsResponse {
+        ok: true,
+        message: Option::default(),
+        num_rooms: num_rooms,
+        num_riddles: num_riddles,
+        max_score: max_score,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn game_map_handler(
+    game_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "game_map_handler(); game_id = {}, username = {}",
+        &game_id_str,
+        &username
+    );
+    let game_id: bson::oid::ObjectId = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db.get_game(&game_id).await {
+        Ok(_) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let rooms: Vec<Room> = match db.get_rooms_of_game(&game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let automap: map::Automap = map::Automap::new(&rooms, &user);
+    let ascii: String = automap.render();
+    let reply: warp::reply::Json = warp::reply::json(&json!(&MapResponse {
+        ok: true,
+        message: Option::default(),
+        game_id,
+        current: automap.current,
+        rooms: automap.rooms,
+        ascii,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn team_highscores_handler(
+    game_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "team_highscores_handler(); game_id = {}, username = {}",
+        &game_id_str,
+        &username
+    );
+    let game_id: ObjectId = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let rooms: Vec<Room> = match db.get_rooms_of_game(&game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let riddles: Vec<Riddle> = match db
+        .get_riddles_by_oids(&db::riddle_ids_of_rooms(&rooms))
+        .await
+    {
+        Ok(riddles) => riddles,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let riddles: HashMap<ObjectId, Riddle> = riddles
+        .into_iter()
+        .map(|riddle| (riddle.id, riddle))
+        .collect();
+    let teams: Vec<Team> = match db.get_teams_by_score().await {
+        Ok(teams) => teams,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    // Only the riddles of this game count, so teams are ranked by the
+    // difficulty of the riddles they have solved in it.
+    let mut highscores: Vec<TeamScoreResponse> = teams
+        .into_iter()
+        .filter_map(|team| {
+            let solved: Vec<&Riddle> = team
+                .solved
+                .iter()
+                .filter_map(|attempt| riddles.get(&attempt.riddle_id))
+                .collect();
+            match solved.is_empty() {
+                true => Option::default(),
+                false => Some(TeamScoreResponse {
+                    name: team.name,
+                    members: team.members,
+                    level: solved.iter().map(|riddle| riddle.level).max().unwrap_or(0),
+                    score: solved.iter().map(|riddle| riddle.difficulty).sum(),
+                }),
+            }
+        })
+        .collect();
+    highscores.sort_by(|a, b| b.score.cmp(&a.score).then(b.level.cmp(&a.level)));
+    let reply: warp::reply::Json = warp::reply::json(&json!(&TeamHighscoresResponse {
+        ok: true,
+        message: Option::default(),
+        highscores,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+/// Returns the team of `user`.
+async fn get_team_of(user: &User, db: &DB) -> Result<Team> {
+    match user.team_id {
+        Some(team_id) => db.get_team(&team_id).await,
+        None => Err(Error::NotInTeamError),
+    }
+}
+
+pub async fn team_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("team_handler(); username = {}", &username);
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let team: Team = match get_team_of(&user, &db).await {
+        Ok(team) => team,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&TeamResponse::from(team)));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn team_invitations_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("team_invitations_handler(); username = {}", &username);
+    let teams: Vec<Team> = match db.get_teams_inviting(&username).await {
+        Ok(teams) => teams,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&InvitationsResponse {
+        ok: true,
+        message: Option::default(),
+        teams: teams.into_iter().map(TeamResponse::from).collect(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn team_create_handler(name: String, username: String, db: DB) -> WebResult<impl Reply> {
+    let name = url_escape::decode(&name).into_owned();
+    log::info!(
+        "team_create_handler(); name = {}, username = {}",
+        &name,
+        &username
+    );
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let name: &str = name.trim();
+    if name.is_empty()
+        || name.chars().count() > MAX_TEAM_NAME_LENGTH
+        || name.contains(|c: char| c == '<' || c == '>')
+    {
+        return Err(reject::custom(Error::InvalidTeamNameError));
+    }
+    if user.team_id.is_some() {
+        return Err(reject::custom(Error::AlreadyInTeamError));
+    }
+    let team: Team = Team::new(&name.to_string(), &username);
+    // The player is claimed for the team first, so that concurrent requests
+    // cannot put them into two teams.
+    match db.assign_user_team(&username, &team.id).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    match db.create_team(&team).await {
+        Ok(()) => (),
+        Err(e) => {
+            db.clear_user_team(&username).await.ok();
+            return Err(reject::custom(e));
+        }
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&TeamResponse::from(team)));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn team_invite_handler(
+    invitee: String,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "team_invite_handler(); invitee = {}, username = {}",
+        &invitee,
+        &username
+    );
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let team_id: ObjectId = match user.team_id {
+        Some(team_id) => team_id,
+        None => return Err(reject::custom(Error::NotInTeamError)),
+    };
+    match db.get_user(&invitee).await {
+        Ok(_) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let team: Team = match db.invite_to_team(&team_id, &invitee).await {
+        Ok(team) => team,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&TeamResponse::from(team)));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn team_join_handler(
+    team_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "team_join_handler(); team_id = {}, username = {}",
+        &team_id_str,
+        &username
+    );
+    let team_id: ObjectId = match ObjectId::parse_str(team_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    if user.team_id.is_some() {
+        return Err(reject::custom(Error::AlreadyInTeamError));
+    }
+    match db.assign_user_team(&username, &team_id).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let team: Team = match db.join_team(&team_id, &username).await {
+        Ok(team) => team,
+        Err(e) => {
+            db.clear_user_team(&username).await.ok();
+            return Err(reject::custom(e));
+        }
+    };
+    // The new member gets all doors the team has opened so far.
+    match db
+        .share_team_progress(&vec![username.clone()], &team.solved, team.level)
+        .await
+    {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&TeamResponse::from(team)));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn team_leave_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("team_leave_handler(); username = {}", &username);
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let team_id: ObjectId = match user.team_id {
+        Some(team_id) => team_id,
+        None => return Err(reject::custom(Error::NotInTeamError)),
+    };
+    // Riddles solved so far stay solved for the one leaving.
+    match db.leave_team(&team_id, &username).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    match db.clear_user_team(&username).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let reply: warp::reply::Json = warp::reply::json(&json!(&StatusResponse {
+        ok: true,
+        message: Option::default(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+async fn issue_certificate(
+    game_id: &ObjectId,
+    username: &String,
+    db: &DB,
+) -> Result<(Certificate, String)> {
+    let user: User = match db.get_user(username).await {
+        Ok(user) => user,
+        Err(e) => return Err(e),
+    };
+    let finished: &FinishedGame = match user.finished.iter().find(|f| f.game_id == *game_id) {
+        Some(finished) => finished,
+        None => return Err(Error::GameNotFinishedError),
+    };
+    let game: Game = match db.get_game(game_id).await {
+        Ok(game) => game,
+        Err(e) => return Err(e),
+    };
+    let rooms: Vec<Room> = match db.get_rooms_of_game(game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(e),
+    };
+    let riddles: Vec<Riddle> = match db
+        .get_riddles_by_oids(&db::riddle_ids_of_rooms(&rooms))
+        .await
+    {
+        Ok(riddles) => riddles,
+        Err(e) => return Err(e),
+    };
+    let riddles: HashMap<ObjectId, i32> = riddles
+        .into_iter()
+        .map(|riddle| (riddle.id, riddle.difficulty))
+        .collect();
+    // Only the riddles of this game count, both for the certified score and
+    // for ranking it among the game's finishers.
+    let game_score = |solved: &Vec<RiddleAttempt>| -> i32 {
+        solved
+            .iter()
+            .filter_map(|attempt| riddles.get(&attempt.riddle_id))
+            .sum()
+    };
+    let score: i32 = game_score(&user.solved);
+    let finishers: Vec<UserSolvedData> = match db.get_game_finishers(game_id).await {
+        Ok(finishers) => finishers,
+        Err(e) => return Err(e),
+    };
+    let rank: u32 = finishers
+        .iter()
+        .filter(|finisher| game_score(&finisher.solved) > score)
+        .count() as u32
+        + 1;
+    let certificate = Certificate {
+        username: user.username.clone(),
+        game_id: game.id,
+        game: game.name,
+        finished: finished.timestamp as i64,
+        score,
+        rank,
+        issued: Utc::now().timestamp(),
+    };
+    let signature: String = match certificate.sign() {
+        Ok(signature) => signature,
+        Err(e) => return Err(e),
+    };
+    Ok((certificate, signature))
+}
+
+pub async fn certificate_handler(
+    game_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "certificate_handler(); game_id = {}, username = {}",
+        &game_id_str,
+        &username
+    );
+    let game_id: bson::oid::ObjectId = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let (certificate, signature) = match issue_certificate(&game_id, &username, &db).await {
+        Ok(issued) => issued,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&CertificateResponse {
+        ok: true,
+        message: Option::default(),
+        certificate,
+        signature,
+        public_key: certificate::public_key(),
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn certificate_svg_handler(
+    game_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "certificate_svg_handler(); game_id = {}, username = {}",
+        &game_id_str,
+        &username
+    );
+    let game_id: bson::oid::ObjectId = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let (certificate, signature) = match issue_certificate(&game_id, &username, &db).await {
+        Ok(issued) => issued,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    Ok(warp::reply::with_header(
+        certificate.render_svg(&signature),
+        "Content-Type",
+        "image/svg+xml",
+    ))
+}
+
+pub async fn certificate_verify_handler(body: CertificateVerifyRequest) -> WebResult<impl Reply> {
+    log::info!(
+        "certificate_verify_handler(); username = {}, game_id = {}",
+        &body.certificate.username,
+        &body.certificate.game_id
+    );
+    let valid: bool = match body.certificate.verify(&body.signature) {
+        Ok(valid) => valid,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&CertificateVerifyResponse {
+        ok: true,
+        message: Option::default(),
+        valid,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn promote_user_handler(
+    user_to_promote: String,
+    role: String,
+    username: String,
+    mut db: DB,
+) -> WebResult<impl Reply> {
+    let user_to_promote = url_escape::decode(&user_to_promote).into_owned();
+    let role = Role::from_str(&url_escape::decode(&role).into_owned());
+    log::info!(
+        "promote_user_handler() username = {}, user_to_promote = {}, role = {}",
+        username,
+        user_to_promote,
+        role
+    );
+    if user_to_promote == username {
+        return Err(reject::custom(Error::UserCannotChangeOwnRoleError));
+    }
+    let current_role = match db.get_user_role(&user_to_promote).await {
+        Ok(role) => role,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let user: User = match db.get_user(&username).await {
+        Ok(user) => user,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    if role <= current_role {
+        return Err(reject::custom(Error::CannotChangeToSameRole));
+    }
+    if user.role != Role::Admin {
+        return Err(reject::custom(Error::UnsufficentRightsError));
+    }
+    match db.promote_user(&user_to_promote, &role).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&PromoteUserResponse {
+        ok: true,
+        message: Option::default(),
+        username: user_to_promote,
+        role,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn validate_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("validate_handler(); username = {}", &username);
+    let issues: Vec<ValidationIssue> = match validator::validate_all(&db).await {
+        Ok(issues) => issues,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&ValidationResponse {
+        ok: true,
+        message: Option::default(),
+        issues,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+fn authoring_reply(id: ObjectId, version: u32) -> WithStatus<warp::reply::Json> {
+    let reply: warp::reply::Json = warp::reply::json(&json!(&AuthoringResponse {
+        ok: true,
+        message: Option::default(),
+        id,
+        version,
+    }));
+    warp::reply::with_status(reply, StatusCode::OK)
+}
+
+pub async fn author_games_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("author_games_handler(); username = {}", &username);
+    let games: Vec<Game> = match db.get_games().await {
+        Ok(games) => games,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&AuthoringGamesResponse {
+        ok: true,
+        message: Option::default(),
+        games,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn author_game_get_handler(
+    game_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_game_get_handler(); game_id = {}, username = {}",
+        &game_id_str,
+        &username
+    );
+    let game_id: ObjectId = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let game: Game = match db.get_game(&game_id).await {
+        Ok(game) => game,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let rooms: Vec<Room> = match db.get_rooms_of_game(&game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&AuthoringGameResponse {
+        ok: true,
+        message: Option::default(),
+        game,
+        rooms,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn author_game_create_handler(
+    username: String,
+    mut game: Game,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!("author_game_create_handler(); username = {}", &username);
+    game.id = ObjectId::new();
+    game.version = 0;
+    match authoring::validate_game(&game) {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let audit: AuditEntry = match authoring::audit_entry(
+        &username,
+        AuditAction::Create,
+        &db.coll_games,
+        &game.id,
+        game.version,
+        Some(&game),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db.insert_audited(db.get_games_coll(), &game, &audit).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(game.id, game.version))
+}
+
+pub async fn author_game_update_handler(
+    game_id_str: OidString,
+    username: String,
+    mut game: Game,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_game_update_handler(); game_id = {}, username = {}",
+        &game_id_str,
+        &username
+    );
+    game.id = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    match db.get_game(&game.id).await {
+        Ok(_) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    match authoring::validate_game(&game) {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let version: u32 = game.version;
+    game.version += 1;
+    let audit: AuditEntry = match authoring::audit_entry(
+        &username,
+        AuditAction::Update,
+        &db.coll_games,
+        &game.id,
+        game.version,
+        Some(&game),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db
+        .replace_audited(db.get_games_coll(), &game.id, version, &game, &audit)
+        .await
+    {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(game.id, game.version))
+}
+
+pub async fn author_game_delete_handler(
+    game_id_str: OidString,
+    version: u32,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_game_delete_handler(); game_id = {}, version = {}, username = {}",
+        &game_id_str,
+        version,
+        &username
+    );
+    let game_id: ObjectId = match ObjectId::parse_str(game_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let rooms: Vec<Room> = match db.get_rooms_of_game(&game_id).await {
+        Ok(rooms) => rooms,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    if !rooms.is_empty() {
+        return Err(reject::custom(Error::ContentValidationError(
+            "game still has rooms".to_string(),
+        )));
+    }
+    let audit: AuditEntry = match authoring::audit_entry::<Game>(
+        &username,
+        AuditAction::Delete,
+        &db.coll_games,
+        &game_id,
+        version,
+        Option::default(),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db
+        .delete_audited(db.get_games_coll(), &game_id, version, &audit)
+        .await
+    {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(game_id, version))
+}
+
+pub async fn author_room_get_handler(
+    room_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_room_get_handler(); room_id = {}, username = {}",
+        &room_id_str,
+        &username
+    );
+    let room_id: ObjectId = match ObjectId::parse_str(room_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let room: Room = match db.get_room(&room_id).await {
+        Ok(room) => room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&AuthoringRoomResponse {
+        ok: true,
+        message: Option::default(),
+        room,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn author_room_create_handler(
+    username: String,
+    mut room: Room,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!("author_room_create_handler(); username = {}", &username);
+    room.id = ObjectId::new();
+    room.version = 0;
+    match authoring::validate_room(&db, &room).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let audit: AuditEntry = match authoring::audit_entry(
+        &username,
+        AuditAction::Create,
+        &db.coll_rooms,
+        &room.id,
+        room.version,
+        Some(&room),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db.insert_audited(db.get_rooms_coll(), &room, &audit).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(room.id, room.version))
+}
+
+/// Validates `room` and writes it as the successor of its current version.
+async fn save_room(room: &mut Room, username: &String, db: &DB) -> Result<()> {
+    match authoring::validate_room(db, room).await {
+        Ok(()) => (),
+        Err(e) => return Err(e),
+    }
+    let version: u32 = room.version;
+    room.version += 1;
+    let audit: AuditEntry = match authoring::audit_entry(
+        username,
+        AuditAction::Update,
+        &db.coll_rooms,
+        &room.id,
+        room.version,
+        Some(&*room),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(e),
+    };
+    db.replace_audited(db.get_rooms_coll(), &room.id, version, room, &audit)
+        .await
+}
+
+pub async fn author_room_update_handler(
+    room_id_str: OidString,
+    username: String,
+    mut room: Room,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_room_update_handler(); room_id = {}, username = {}",
+        &room_id_str,
+        &username
+    );
+    room.id = match ObjectId::parse_str(room_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    match db.get_room(&room.id).await {
+        Ok(_) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    match save_room(&mut room, &username, &db).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(room.id, room.version))
+}
+
+pub async fn author_room_delete_handler(
+    room_id_str: OidString,
+    version: u32,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_room_delete_handler(); room_id = {}, version = {}, username = {}",
+        &room_id_str,
+        version,
+        &username
+    );
+    let room_id: ObjectId = match ObjectId::parse_str(room_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let room: Room = match db.get_room(&room_id).await {
+        Ok(room) => room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db.is_room_referenced(&room).await {
+        Ok(false) => (),
+        Ok(true) => return Err(reject::custom(Error::RoomInUseError)),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    let audit: AuditEntry = match authoring::audit_entry::<Room>(
+        &username,
+        AuditAction::Delete,
+        &db.coll_rooms,
+        &room_id,
+        version,
+        Option::default(),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db
+        .delete_audited(db.get_rooms_coll(), &room_id, version, &audit)
+        .await
+    {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(room_id, version))
+}
+
+pub async fn author_direction_put_handler(
+    room_id_str: OidString,
+    version: u32,
+    username: String,
+    direction: Direction,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_direction_put_handler(); room_id = {}, direction = {}, username = {}",
+        &room_id_str,
+        &direction.direction,
+        &username
+    );
+    let room_id: ObjectId = match ObjectId::parse_str(room_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let mut room: Room = match db.get_room(&room_id).await {
+        Ok(room) => room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    if room.version != version {
+        return Err(reject::custom(Error::VersionConflictError));
+    }
+    match room
+        .neighbors
+        .iter()
+        .position(|neighbor| neighbor.direction == direction.direction)
+    {
+        Some(idx) => room.neighbors[idx] = direction,
+        None => room.neighbors.push(direction),
+    }
+    match save_room(&mut room, &username, &db).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(room.id, room.version))
+}
+
+pub async fn author_direction_delete_handler(
+    room_id_str: OidString,
+    direction_str: String,
+    version: u32,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_direction_delete_handler(); room_id = {}, direction = {}, username = {}",
+        &room_id_str,
+        &direction_str,
+        &username
+    );
+    let room_id: ObjectId = match ObjectId::parse_str(room_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let mut room: Room = match db.get_room(&room_id).await {
+        Ok(room) => room,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    if room.version != version {
+        return Err(reject::custom(Error::VersionConflictError));
+    }
+    match room
+        .neighbors
+        .iter()
+        .position(|neighbor| neighbor.direction == direction_str)
+    {
+        Some(idx) => room.neighbors.remove(idx),
+        None => return Err(reject::custom(Error::NeighborNotFoundError)),
+    };
+    match save_room(&mut room, &username, &db).await {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(room.id, room.version))
+}
+
+pub async fn author_riddle_get_handler(
+    riddle_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_riddle_get_handler(); riddle_id = {}, username = {}",
+        &riddle_id_str,
+        &username
+    );
+    let riddle_id: ObjectId = match ObjectId::parse_str(riddle_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let riddle: Riddle = match db.get_riddle_by_oid(&riddle_id).await {
+        Ok(Some(riddle)) => riddle,
+        Ok(None) => return Err(reject::custom(Error::RiddleNotFoundError)),
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&AuthoringRiddleResponse {
+        ok: true,
+        message: Option::default(),
+        riddle,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn author_ratings_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("author_ratings_handler(); username = {}", &username);
+    let ratings: Vec<RiddleRating> = match db.get_riddle_ratings(None).await {
+        Ok(ratings) => ratings,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&RatingsResponse {
+        ok: true,
+        message: Option::default(),
+        ratings,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn author_riddle_ratings_handler(
+    riddle_id_str: OidString,
+    username: String,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!(
+        "author_riddle_ratings_handler(); riddle_id = {}, username = {}",
+        &riddle_id_str,
+        &username
+    );
+    let riddle_id: ObjectId = match ObjectId::parse_str(riddle_id_str) {
+        Ok(oid) => oid,
+        Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
+    };
+    let rating: Option<RiddleRating> = match db.get_riddle_ratings(Some(&riddle_id)).await {
+        Ok(ratings) => ratings.into_iter().next(),
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let feedback: Vec<RiddleFeedback> = match db.get_riddle_feedback(&riddle_id).await {
+        Ok(feedback) => feedback,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    let reply: warp::reply::Json = warp::reply::json(&json!(&RiddleRatingsResponse {
+        ok: true,
+        message: Option::default(),
+        rating,
+        feedback,
+    }));
+    Ok(warp::reply::with_status(reply, StatusCode::OK))
+}
+
+pub async fn author_riddle_create_handler(
+    username: String,
+    mut riddle: Riddle,
+    db: DB,
+) -> WebResult<impl Reply> {
+    log::info!("author_riddle_create_handler(); username = {}", &username);
+    riddle.id = ObjectId::new();
+    riddle.version = 0;
+    match authoring::validate_riddle(&riddle) {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    if let Some(ref script) = riddle.script {
+        match validate_script(riddle.script_engine.unwrap_or_default(), script).await {
+            Ok(()) => (),
+            Err(e) => return Err(reject::custom(e)),
+        }
+    }
+    let audit: AuditEntry = match authoring::audit_entry(
+        &username,
+        AuditAction::Create,
+        &db.coll_riddles,
+        &riddle.id,
+        riddle.version,
+        Some(&riddle),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match db
+        .insert_audited(db.get_riddles_coll(), &riddle, &audit)
+        .await
+    {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(riddle.id, riddle.version))
+}
+
+pub async fn author_riddle_update_handler(
+    riddle_id_str: OidString,
     username: String,
+    mut riddle: Riddle,
     db: DB,
 ) -> WebResult<impl Reply> {
     log::info!(
-        "debriefing_get_by_riddle_id_handler(); riddle_id = {}, username = {}",
+        "author_riddle_update_handler(); riddle_id = {}, username = {}",
         &riddle_id_str,
         &username
     );
-    let oid: bson::oid::ObjectId = match ObjectId::parse_str(riddle_id_str) {
+    riddle.id = match ObjectId::parse_str(riddle_id_str) {
         Ok(oid) => oid,
         Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
     };
-    let solved_riddle: Option<Riddle> = match db.get_riddle_if_solved(&oid, &username, None).await {
-        Ok(riddle) => riddle,
+    let current: Riddle = match db.get_riddle_by_oid(&riddle.id).await {
+        Ok(Some(riddle)) => riddle,
+        Ok(None) => return Err(reject::custom(Error::RiddleNotFoundError)),
         Err(e) => return Err(reject::custom(e)),
     };
-    let riddle: Riddle = match solved_riddle {
-        Some(riddle) => riddle,
-        None => return Err(reject::custom(Error::RiddleNotFoundError)),
+    match authoring::validate_riddle(&riddle) {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    if let Some(ref script) = riddle.script {
+        match validate_script(riddle.script_engine.unwrap_or_default(), script).await {
+            Ok(()) => (),
+            Err(e) => return Err(reject::custom(e)),
+        }
+    }
+    // The doors guarded by the riddle carry its level, so it must not change
+    // as long as the riddle is in use.
+    if riddle.level != current.level {
+        let rooms: Vec<Room> = match db.get_rooms_by_riddle(&riddle.id).await {
+            Ok(rooms) => rooms,
+            Err(e) => return Err(reject::custom(e)),
+        };
+        if !rooms.is_empty() {
+            return Err(reject::custom(Error::ContentValidationError(
+                "cannot change the level of a riddle that guards a door".to_string(),
+            )));
+        }
+    }
+    let version: u32 = riddle.version;
+    riddle.version += 1;
+    let audit: AuditEntry = match authoring::audit_entry(
+        &username,
+        AuditAction::Update,
+        &db.coll_riddles,
+        &riddle.id,
+        riddle.version,
+        Some(&riddle),
+    ) {
+        Ok(audit) => audit,
+        Err(e) => return Err(reject::custom(e)),
     };
-    log::info!("got riddle {}", riddle.level);
-    let reply: warp::reply::Json = warp::reply::json(&json!(&DebriefingResponse {
-        ok: true,
-        message: Option::default(),
-        debriefing: riddle.debriefing,
-    }));
-    Ok(warp::reply::with_status(reply, StatusCode::OK))
+    match db
+        .replace_audited(db.get_riddles_coll(), &riddle.id, version, &riddle, &audit)
+        .await
+    {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(riddle.id, riddle.version))
 }
 
-pub async fn game_stats_handler(
-    game_id_str: String,
+pub async fn author_riddle_delete_handler(
+    riddle_id_str: OidString,
+    version: u32,
     username: String,
     db: DB,
 ) -> WebResult<impl Reply> {
     log::info!(
-        "game_stats_handler(); game_id = {}, username = {}",
-        &game_id_str,
+        "author_riddle_delete_handler(); riddle_id = {}, version = {}, username = {}",
+        &riddle_id_str,
+        version,
         &username
     );
-    let game_id: bson::oid::ObjectId = match ObjectId::parse_str(game_id_str) {
+    let riddle_id: ObjectId = match ObjectId::parse_str(riddle_id_str) {
         Ok(oid) => oid,
         Err(e) => return Err(reject::custom(Error::BsonOidError(e))),
     };
-    let num_rooms: u32 = match db.get_num_rooms(&game_id).await {
-        Ok(num_rooms) => num_rooms,
+    let rooms: Vec<Room> = match db.get_rooms_by_riddle(&riddle_id).await {
+        Ok(rooms) => rooms,
         Err(e) => return Err(reject::custom(e)),
     };
-    let num_riddles: u32 = match db.get_num_riddles(&game_id).await {
-        Ok(num_riddles) => num_riddles,
+    if !rooms.is_empty() {
+        return Err(reject::custom(Error::ContentValidationError(
+            "riddle still guards a door".to_string(),
+        )));
+    }
+    let audit: AuditEntry = match authoring::audit_entry::<Riddle>(
+        &username,
+        AuditAction::Delete,
+        &db.coll_riddles,
+        &riddle_id,
+        version,
+        Option::default(),
+    ) {
+        Ok(audit) => audit,
         Err(e) => return Err(reject::custom(e)),
     };
-    let max_score: u32 = match db.get_max_score_for_game(&game_id).await {
-        Ok(max_score) => max_score,
+    match db
+        .delete_audited(db.get_riddles_coll(), &riddle_id, version, &audit)
+        .await
+    {
+        Ok(()) => (),
+        Err(e) => return Err(reject::custom(e)),
+    }
+    Ok(authoring_reply(riddle_id, version))
+}
+
+pub async fn audit_handler(username: String, db: DB) -> WebResult<impl Reply> {
+    log::info!("audit_handler(); username = {}", &username);
+    let entries: Vec<AuditEntry> = match db.get_audit_entries(100).await {
+        Ok(entries) => entries,
         Err(e) => return Err(reject::custom(e)),
     };
-    let reply: warp::reply::Json = warp::reply::json(&json!(&GameStatsResponse {
+    let reply: warp::reply::Json = warp::reply::json(&json!(&AuditResponse {
         ok: true,
         message: Option::default(),
-        num_rooms: num_rooms,
-        num_riddles: num_riddles,
-        max_score: max_score,
+        entries,
     }));
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
 
-pub async fn promote_user_handler(
-    user_to_promote: String,
-    role: String,
+pub async fn script_stats_handler(
     username: String,
-    mut db: DB,
+    script_env: Arc<Mutex<ScriptEnvMap>>,
 ) -> WebResult<impl Reply> {
-    let user_to_promote = url_escape::decode(&user_to_promote).into_owned();
-    let role = Role::from_str(&url_escape::decode(&role).into_owned());
-    log::info!(
-        "promote_user_handler() username = {}, user_to_promote = {}, role = {}",
-        username,
-        user_to_promote,
-        role
-    );
-    if user_to_promote == username {
-        return Err(reject::custom(Error::UserCannotChangeOwnRoleError));
-    }
-    let current_role = match db.get_user_role(&user_to_promote).await {
-        Ok(role) => role,
-        Err(e) => return Err(reject::custom(e)),
-    };
-    let user: User = match db.get_user(&username).await {
-        Ok(user) => user,
-        Err(e) => return Err(reject::custom(e)),
-    };
-    if role <= current_role {
-        return Err(reject::custom(Error::CannotChangeToSameRole));
-    }
-    if user.role != Role::Admin {
-        return Err(reject::custom(Error::UnsufficentRightsError));
-    }
-    match db.promote_user(&user_to_promote, &role).await {
-        Ok(()) => (),
-        Err(e) => return Err(reject::custom(e)),
-    };
-    let reply: warp::reply::Json = warp::reply::json(&json!(&PromoteUserResponse {
+    log::info!("script_stats_handler(); username = {}", &username);
+    let stats: ScriptEnvStats = script_env.lock().unwrap().stats();
+    let reply: warp::reply::Json = warp::reply::json(&json!(&ScriptStatsResponse {
         ok: true,
         message: Option::default(),
-        username: user_to_promote,
-        role,
+        stats,
     }));
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
@@ -1843,6 +3819,64 @@ pub async fn webauthn_login_finish_handler(
     Ok(warp::reply::with_status(reply, StatusCode::OK))
 }
 
+async fn run_command(args: &[String], db: &DB) -> Result<()> {
+    match args {
+        [command, path] if command == "import" => game_file::import(db, path).await,
+        [command, game_id, path] if command == "export" => {
+            let game_id: ObjectId = match ObjectId::parse_str(game_id) {
+                Ok(oid) => oid,
+                Err(e) => return Err(Error::BsonOidError(e)),
+            };
+            game_file::export(db, &game_id, path).await
+        }
+        [command] if command == "validate" => {
+            let issues: Vec<ValidationIssue> = match validator::validate_all(db).await {
+                Ok(issues) => issues,
+                Err(e) => return Err(e),
+            };
+            for issue in issues.iter() {
+                println!("{} {:?}: {}", issue.game_id, issue.kind, issue.message);
+            }
+            match issues.is_empty() {
+                true => Ok(()),
+                false => Err(Error::GameValidationError),
+            }
+        }
+        [command, width, height, topology, seed, riddles, path] if command == "generate" => {
+            let (width, height, seed) = match (width.parse(), height.parse(), seed.parse()) {
+                (Ok(width), Ok(height), Ok(seed)) => (width, height, seed),
+                _ => return Err(Error::UnknownCommandError),
+            };
+            let topology: Topology = match Topology::from_str(topology) {
+                Some(topology) => topology,
+                None => return Err(Error::UnknownCommandError),
+            };
+            let pool: Vec<Riddle> = match game_file::read_riddles(riddles) {
+                Ok(pool) => pool,
+                Err(e) => return Err(e),
+            };
+            let options = GeneratorOptions {
+                name: format!("Labyrinth #{}", seed),
+                width,
+                height,
+                topology,
+                seed,
+            };
+            match generator::generate(&options, pool) {
+                Ok(game_file) => game_file.write(path),
+                Err(e) => Err(e),
+            }
+        }
+        _ => {
+            eprintln!(
+                "Usage: {} [import <file> | export <game_id> <file> | validate | generate <width> <height> <perfect|braided|loops> <seed> <riddles> <file>]",
+                env!("CARGO_PKG_NAME")
+            );
+            Err(Error::UnknownCommandError)
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::init();
@@ -1856,7 +3890,20 @@ async fn main() -> Result<()> {
         .run_command(doc! {"ping": 1}, None)
         .await?;
     log::info!("Connected successfully.");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        return run_command(&args, &db).await;
+    }
     let script_env = Arc::new(Mutex::new(ScriptEnvMap::new()));
+    let idle_script_env = script_env.clone();
+    tokio::task::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            idle_script_env.lock().unwrap().expire();
+        }
+    });
+    let hub: Hub = Arc::new(Mutex::new(LiveHub::new()));
     let root = warp::path::end().map(|| "Labyrinth API root.");
     /* Routes accessible to all users */
     let ping_route = warp::path!("ping").and(warp::get()).and_then(ping_handler);
@@ -1906,6 +3953,10 @@ async fn main() -> Result<()> {
             .and(warp::body::json())
             .and(with_db(db.clone()))
             .and_then(webauthn_login_finish_handler);
+    let certificate_verify_route = warp::path!("certificate" / "verify")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(certificate_verify_handler);
     /* Routes accessible only to authorized users */
     let webauthn_register_start_route = warp::path!("user" / "webauthn" / "register" / "start")
         .and(warp::post())
@@ -1929,6 +3980,7 @@ async fn main() -> Result<()> {
         .and_then(user_whoami_handler);
     let riddle_get_by_oid_route = warp::path!("riddle" / OidString)
         .and(warp::get())
+        .and(warp::header::optional::<String>("accept-language"))
         .and(with_auth(Role::User))
         .and(with_db(db.clone()))
         .and(with_script_env(script_env.clone()))
@@ -1938,52 +3990,364 @@ async fn main() -> Result<()> {
         .and(with_auth(Role::User))
         .and(with_db(db.clone()))
         .and_then(debriefing_get_by_riddle_id_handler);
+    let riddle_attachment_route = warp::path!("riddle" / OidString / "attachment" / usize)
+        .and(warp::get())
+        .and(warp::header::optional::<String>("accept-language"))
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and(with_script_env(script_env.clone()))
+        .and_then(riddle_attachment_handler);
+    let riddle_feedback_route = warp::path!("riddle" / OidString / "feedback")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(riddle_feedback_handler);
     let riddle_solve_route = warp::path!("riddle" / "solve" / OidString)
         .and(warp::post())
         .and(warp::body::json())
+        .and(warp::header::optional::<String>("accept-language"))
         .and(with_auth(Role::User))
         .and(with_db(db.clone()))
         .and(with_script_env(script_env.clone()))
+        .and(with_hub(hub.clone()))
         .and_then(riddle_solve_handler);
     let go_route = warp::path!("go" / String)
         .and(warp::get())
         .and(with_auth(Role::User))
         .and(with_db(db.clone()))
+        .and(with_hub(hub.clone()))
         .and_then(go_handler);
+    let live_route = warp::path!("live")
+        .and(warp::ws())
+        .and(with_token_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and(with_hub(hub.clone()))
+        .and_then(live_handler);
     let highscores_route = warp::path!("game" / "highscores" / OidString)
         .and(warp::get())
         .and(with_auth(Role::User))
         .and(with_db(db.clone()))
         .and_then(highscores_handler);
+    let team_highscores_route = warp::path!("game" / "highscores" / "teams" / OidString)
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(team_highscores_handler);
+    let team_route = warp::path!("team")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(team_handler);
+    let team_invitations_route = warp::path!("team" / "invitations")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(team_invitations_handler);
+    let team_create_route = warp::path!("team" / "create" / String)
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(team_create_handler);
+    let team_invite_route = warp::path!("team" / "invite" / String)
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(team_invite_handler);
+    let team_join_route = warp::path!("team" / "join" / OidString)
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(team_join_handler);
+    let team_leave_route = warp::path!("team" / "leave")
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(team_leave_handler);
     let game_stats_route = warp::path!("game" / "stats" / OidString)
         .and(warp::get())
         .and(with_auth(Role::User))
         .and(with_db(db.clone()))
         .and_then(game_stats_handler);
+    let game_map_route = warp::path!("game" / OidString / "map")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(game_map_handler);
+    let certificate_route = warp::path!("game" / OidString / "certificate")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(certificate_handler);
+    let certificate_svg_route = warp::path!("game" / OidString / "certificate" / "svg")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(certificate_svg_handler);
+    let room_look_route = warp::path!("room" / "look")
+        .and(warp::get())
+        .and(warp::header::optional::<String>("accept-language"))
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(room_look_handler);
+    let room_examine_route = warp::path!("room" / "examine" / String)
+        .and(warp::get())
+        .and(warp::header::optional::<String>("accept-language"))
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(room_examine_handler);
+    let notes_route = warp::path!("room" / "notes")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(notes_handler);
+    let note_add_route = warp::path!("room" / "note")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(note_add_handler);
+    let note_report_route = warp::path!("note" / OidString / "report")
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(note_report_handler);
+    let travel_route = warp::path!("travel" / OidString)
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and(with_hub(hub.clone()))
+        .and_then(travel_handler);
+    let route_entrance_route = warp::path!("route" / "entrance")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(route_entrance_handler);
+    let inventory_route = warp::path!("inventory")
+        .and(warp::get())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(inventory_handler);
+    let item_take_route = warp::path!("item" / "take" / String)
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(item_take_handler);
+    let item_drop_route = warp::path!("item" / "drop" / String)
+        .and(warp::post())
+        .and(with_auth(Role::User))
+        .and(with_db(db.clone()))
+        .and_then(item_drop_handler);
     let cheat_route = warp::path!("cheat")
         .and(warp::get())
         .and(with_auth(Role::User))
         .and_then(cheat_handler);
+    /* Routes accessible to designers and admins */
+    let author_games_route = warp::path!("author" / "game")
+        .and(warp::get())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_games_handler);
+    let author_game_create_route = warp::path!("author" / "game")
+        .and(warp::post())
+        .and(with_auth(Role::Designer))
+        .and(warp::body::json())
+        .and(with_db(db.clone()))
+        .and_then(author_game_create_handler);
+    let author_game_get_route = warp::path!("author" / "game" / OidString)
+        .and(warp::get())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_game_get_handler);
+    let author_game_update_route = warp::path!("author" / "game" / OidString)
+        .and(warp::put())
+        .and(with_auth(Role::Designer))
+        .and(warp::body::json())
+        .and(with_db(db.clone()))
+        .and_then(author_game_update_handler);
+    let author_game_delete_route = warp::path!("author" / "game" / OidString / u32)
+        .and(warp::delete())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_game_delete_handler);
+    let author_room_create_route = warp::path!("author" / "room")
+        .and(warp::post())
+        .and(with_auth(Role::Designer))
+        .and(warp::body::json())
+        .and(with_db(db.clone()))
+        .and_then(author_room_create_handler);
+    let author_room_get_route = warp::path!("author" / "room" / OidString)
+        .and(warp::get())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_room_get_handler);
+    let author_room_update_route = warp::path!("author" / "room" / OidString)
+        .and(warp::put())
+        .and(with_auth(Role::Designer))
+        .and(warp::body::json())
+        .and(with_db(db.clone()))
+        .and_then(author_room_update_handler);
+    let author_room_delete_route = warp::path!("author" / "room" / OidString / u32)
+        .and(warp::delete())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_room_delete_handler);
+    let author_direction_put_route = warp::path!("author" / "room" / OidString / "direction" / u32)
+        .and(warp::put())
+        .and(with_auth(Role::Designer))
+        .and(warp::body::json())
+        .and(with_db(db.clone()))
+        .and_then(author_direction_put_handler);
+    let author_direction_delete_route =
+        warp::path!("author" / "room" / OidString / "direction" / String / u32)
+            .and(warp::delete())
+            .and(with_auth(Role::Designer))
+            .and(with_db(db.clone()))
+            .and_then(author_direction_delete_handler);
+    let author_riddle_create_route = warp::path!("author" / "riddle")
+        .and(warp::post())
+        .and(with_auth(Role::Designer))
+        .and(warp::body::json())
+        .and(with_db(db.clone()))
+        .and_then(author_riddle_create_handler);
+    let author_riddle_get_route = warp::path!("author" / "riddle" / OidString)
+        .and(warp::get())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_riddle_get_handler);
+    let author_riddle_update_route = warp::path!("author" / "riddle" / OidString)
+        .and(warp::put())
+        .and(with_auth(Role::Designer))
+        .and(warp::body::json())
+        .and(with_db(db.clone()))
+        .and_then(author_riddle_update_handler);
+    let author_riddle_delete_route = warp::path!("author" / "riddle" / OidString / u32)
+        .and(warp::delete())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_riddle_delete_handler);
+    let author_ratings_route = warp::path!("author" / "ratings")
+        .and(warp::get())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_ratings_handler);
+    let author_riddle_ratings_route = warp::path!("author" / "riddle" / OidString / "ratings")
+        .and(warp::get())
+        .and(with_auth(Role::Designer))
+        .and(with_db(db.clone()))
+        .and_then(author_riddle_ratings_handler);
+    let author_routes = author_games_route
+        .or(author_game_create_route)
+        .or(author_game_get_route)
+        .or(author_game_update_route)
+        .or(author_game_delete_route)
+        .or(author_room_create_route)
+        .or(author_room_get_route)
+        .or(author_room_update_route)
+        .or(author_room_delete_route)
+        .or(author_direction_put_route)
+        .or(author_direction_delete_route)
+        .or(author_riddle_create_route)
+        .or(author_riddle_get_route)
+        .or(author_riddle_update_route)
+        .or(author_riddle_delete_route)
+        .or(author_ratings_route)
+        .or(author_riddle_ratings_route);
     /* Routes accessible only to authorized admins */
     let riddle_get_by_level_route = warp::path!("admin" / "riddle" / "by" / "level" / u32)
         .and(warp::get())
+        .and(warp::header::optional::<String>("accept-language"))
         .and(with_auth(Role::Admin))
         .and(with_db(db.clone()))
         .and(with_script_env(script_env.clone()))
         .and_then(riddle_get_by_level_handler);
+    let validate_route = warp::path!("admin" / "validate")
+        .and(warp::get())
+        .and(with_auth(Role::Admin))
+        .and(with_db(db.clone()))
+        .and_then(validate_handler);
+    let audit_route = warp::path!("admin" / "audit")
+        .and(warp::get())
+        .and(with_auth(Role::Admin))
+        .and(with_db(db.clone()))
+        .and_then(audit_handler);
+    let script_stats_route = warp::path!("admin" / "scripts")
+        .and(warp::get())
+        .and(with_auth(Role::Admin))
+        .and(with_script_env(script_env.clone()))
+        .and_then(script_stats_handler);
     let promote_user_route = warp::path!("admin" / "promote" / String / String)
         .and(warp::get())
         .and(with_auth(Role::Admin))
         .and(with_db(db.clone()))
         .and_then(promote_user_handler);
+    let reported_notes_route = warp::path!("admin" / "notes" / "reported")
+        .and(warp::get())
+        .and(with_auth(Role::Admin))
+        .and(with_db(db.clone()))
+        .and_then(reported_notes_handler);
+    let note_hide_route = warp::path!("admin" / "note" / OidString / "hide")
+        .and(warp::post())
+        .and(warp::any().map(|| true))
+        .and(with_auth(Role::Admin))
+        .and(with_db(db.clone()))
+        .and_then(note_hide_handler);
+    let note_unhide_route = warp::path!("admin" / "note" / OidString / "unhide")
+        .and(warp::post())
+        .and(warp::any().map(|| false))
+        .and(with_auth(Role::Admin))
+        .and(with_db(db.clone()))
+        .and_then(note_hide_handler);
+    let chat_mute_route = warp::path!("admin" / "chat" / "mute" / String)
+        .and(warp::post())
+        .and(warp::any().map(|| true))
+        .and(with_auth(Role::Admin))
+        .and(with_hub(hub.clone()))
+        .and_then(chat_mute_handler);
+    let chat_unmute_route = warp::path!("admin" / "chat" / "unmute" / String)
+        .and(warp::post())
+        .and(warp::any().map(|| false))
+        .and(with_auth(Role::Admin))
+        .and(with_hub(hub.clone()))
+        .and_then(chat_mute_handler);
+    let note_routes = notes_route
+        .or(note_add_route)
+        .or(note_report_route)
+        .or(reported_notes_route)
+        .or(note_hide_route)
+        .or(note_unhide_route);
+    let team_routes = team_route
+        .or(team_invitations_route)
+        .or(team_create_route)
+        .or(team_invite_route)
+        .or(team_join_route)
+        .or(team_leave_route)
+        .or(team_highscores_route);
 
     let routes = root
         .or(riddle_get_by_oid_route)
         .or(debriefing_get_by_riddle_id_route)
+        .or(riddle_feedback_route)
+        .or(riddle_attachment_route)
         .or(riddle_get_by_level_route)
         .or(promote_user_route)
+        .or(chat_mute_route)
+        .or(chat_unmute_route)
+        .or(validate_route)
+        .or(audit_route)
+        .or(script_stats_route)
+        .or(author_routes)
+        .or(note_routes)
         .or(riddle_solve_route)
         .or(go_route)
+        .or(live_route)
+        .or(room_look_route)
+        .or(room_examine_route)
+        .or(travel_route)
+        .or(route_entrance_route)
+        .or(inventory_route)
+        .or(item_take_route)
+        .or(item_drop_route)
         .or(user_whoami_route)
         .or(user_auth_route)
         .or(user_login_route)
@@ -2000,7 +4364,12 @@ async fn main() -> Result<()> {
         .or(ping_route)
         .or(cheat_route)
         .or(highscores_route)
+        .or(team_routes)
         .or(game_stats_route)
+        .or(game_map_route)
+        .or(certificate_route)
+        .or(certificate_svg_route)
+        .or(certificate_verify_route)
         .or(warp::any().and(warp::options()).map(warp::reply))
         .recover(error::handle_rejection);
 
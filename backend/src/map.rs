@@ -0,0 +1,180 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::db::{Room, User};
+use crate::generator::parse_coords;
+use crate::validator::destination;
+use bson::oid::ObjectId;
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+pub struct MapDoor {
+    pub direction: String,
+    /// The player can pass the door, see `Direction::is_open_for`.
+    pub open: bool,
+    /// The room behind the door, if the player has been there.
+    pub room_id: Option<ObjectId>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MapRoom {
+    pub id: ObjectId,
+    pub number: u32,
+    pub coords: Option<String>,
+    pub entry: bool,
+    pub exit: bool,
+    pub doors: Vec<MapDoor>,
+}
+
+/// What a player knows about a game: the rooms they have visited and the
+/// doors of these rooms. Everything else is hidden in the fog of war.
+#[derive(Serialize, Debug)]
+pub struct Automap {
+    pub current: Option<ObjectId>,
+    pub rooms: Vec<MapRoom>,
+}
+
+impl Automap {
+    /// Builds the map of `user` from all `rooms` of a game.
+    pub fn new(rooms: &Vec<Room>, user: &User) -> Automap {
+        let visited = |room: &Room| -> bool {
+            user.rooms_entered.contains(&room.id) || user.in_room == Some(room.id)
+        };
+        let map_rooms: Vec<MapRoom> = rooms
+            .iter()
+            .filter(|room| visited(room))
+            .map(|room| MapRoom {
+                id: room.id,
+                number: room.number,
+                coords: room.coords.clone(),
+                entry: room.entry.unwrap_or(false),
+                exit: room.exit.unwrap_or(false),
+                doors: room
+                    .neighbors
+                    .iter()
+                    .map(|neighbor| MapDoor {
+                        direction: neighbor.direction.clone(),
+                        open: neighbor.is_open_for(user),
+                        room_id: destination(rooms, room, neighbor)
+                            .map(|idx| &rooms[idx])
+                            .filter(|behind| visited(behind))
+                            .map(|behind| behind.id),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Automap {
+            current: user
+                .in_room
+                .filter(|in_room| map_rooms.iter().any(|room| &room.id == in_room)),
+            rooms: map_rooms,
+        }
+    }
+
+    /// Renders the map with box-drawing characters. Each room is a cell
+    /// three characters wide; open doors are gaps in the walls, closed doors
+    /// are dashed. Inside a cell, `@` marks the current position, `>` the
+    /// entry and `<` the exit; `^`, `v` and `↕` mark doors up and down, and
+    /// `+` marks other exits. Rooms without spreadsheet-like coordinates
+    /// cannot be placed on the grid and are left out.
+    pub fn render(&self) -> String {
+        let placed: Vec<(&MapRoom, (usize, usize))> = self
+            .rooms
+            .iter()
+            .filter_map(|room| {
+                room.coords
+                    .as_ref()
+                    .and_then(|coords| parse_coords(coords))
+                    .map(|xy| (room, xy))
+            })
+            .collect();
+        if placed.is_empty() {
+            return String::new();
+        }
+        let min_x: usize = placed.iter().map(|(_, (x, _))| *x).min().unwrap_or(0);
+        let min_y: usize = placed.iter().map(|(_, (_, y))| *y).min().unwrap_or(0);
+        let max_x: usize = placed.iter().map(|(_, (x, _))| *x).max().unwrap_or(0);
+        let max_y: usize = placed.iter().map(|(_, (_, y))| *y).max().unwrap_or(0);
+        let width: usize = 4 * (max_x - min_x + 1) + 1;
+        let height: usize = 2 * (max_y - min_y + 1) + 1;
+        let mut canvas: Vec<Vec<char>> = vec![vec![' '; width]; height];
+        for (room, (x, y)) in placed.iter() {
+            let col: usize = 4 * (x - min_x);
+            let row: usize = 2 * (y - min_y);
+            let door = |direction: &str| room.doors.iter().find(|d| d.direction == direction);
+            for i in 1..4 {
+                canvas[row][col + i] = '─';
+                canvas[row + 2][col + i] = '─';
+            }
+            canvas[row + 1][col] = '│';
+            canvas[row + 1][col + 4] = '│';
+            for (direction, r, c, closed) in [
+                ("n", row, col + 2, '┄'),
+                ("s", row + 2, col + 2, '┄'),
+                ("w", row + 1, col, '┆'),
+                ("e", row + 1, col + 4, '┆'),
+            ] {
+                if let Some(door) = door(direction) {
+                    canvas[r][c] = match door.open {
+                        true => ' ',
+                        false => closed,
+                    };
+                }
+            }
+            canvas[row + 1][col + 1] = match room
+                .doors
+                .iter()
+                .any(|d| !["n", "e", "s", "w", "u", "d"].contains(&d.direction.as_str()))
+            {
+                true => '+',
+                false => ' ',
+            };
+            canvas[row + 1][col + 2] = match (Some(room.id) == self.current, room.entry, room.exit)
+            {
+                (true, _, _) => '@',
+                (false, _, true) => '<',
+                (false, true, _) => '>',
+                _ => ' ',
+            };
+            canvas[row + 1][col + 3] = match (door("u").is_some(), door("d").is_some()) {
+                (true, true) => '↕',
+                (true, false) => '^',
+                (false, true) => 'v',
+                _ => ' ',
+            };
+        }
+        // Corners are joined according to the walls around them.
+        for row in (0..height).step_by(2) {
+            for col in (0..width).step_by(4) {
+                let up: bool = row > 0 && ['│', '┆'].contains(&canvas[row - 1][col]);
+                let down: bool = row + 1 < height && ['│', '┆'].contains(&canvas[row + 1][col]);
+                let left: bool = col > 0 && canvas[row][col - 1] == '─';
+                let right: bool = col + 1 < width && canvas[row][col + 1] == '─';
+                canvas[row][col] = match (up, down, left, right) {
+                    (true, true, true, true) => '┼',
+                    (true, true, true, false) => '┤',
+                    (true, true, false, true) => '├',
+                    (true, false, true, true) => '┴',
+                    (false, true, true, true) => '┬',
+                    (true, true, false, false) => '│',
+                    (false, false, true, true) => '─',
+                    (true, false, true, false) => '┘',
+                    (true, false, false, true) => '└',
+                    (false, true, true, false) => '┐',
+                    (false, true, false, true) => '┌',
+                    (true, false, false, false) => '╵',
+                    (false, true, false, false) => '╷',
+                    (false, false, true, false) => '╴',
+                    (false, false, false, true) => '╶',
+                    (false, false, false, false) => ' ',
+                };
+            }
+        }
+        canvas
+            .iter()
+            .map(|line| line.iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
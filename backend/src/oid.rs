@@ -0,0 +1,49 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use bson::oid::ObjectId;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S: Serializer>(v: &ObjectId, s: S) -> Result<S::Ok, S::Error> {
+    let hex: String = v.to_hex();
+    String::serialize(&hex, s)
+}
+pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<ObjectId, D::Error> {
+    let hex: String = String::deserialize(d)?;
+    ObjectId::parse_str(&hex).map_err(|e| serde::de::Error::custom(e))
+}
+
+pub mod option {
+    use bson::oid::ObjectId;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Option<ObjectId>, s: S) -> Result<S::Ok, S::Error> {
+        let hex: Option<String> = v.map(|oid| oid.to_hex());
+        Option::<String>::serialize(&hex, s)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<ObjectId>, D::Error> {
+        match Option::<String>::deserialize(d)? {
+            Some(hex) => ObjectId::parse_str(&hex)
+                .map(Some)
+                .map_err(|e| serde::de::Error::custom(e)),
+            None => Ok(None),
+        }
+    }
+}
+
+pub mod vec {
+    use bson::oid::ObjectId;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Vec<ObjectId>, s: S) -> Result<S::Ok, S::Error> {
+        let hex: Vec<String> = v.iter().map(|oid| oid.to_hex()).collect();
+        Vec::<String>::serialize(&hex, s)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<ObjectId>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|hex| ObjectId::parse_str(hex).map_err(|e| serde::de::Error::custom(e)))
+            .collect()
+    }
+}
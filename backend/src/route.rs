@@ -0,0 +1,72 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::db::{Room, User};
+use crate::validator::destination;
+use bson::oid::ObjectId;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RouteStep {
+    /// The door taken to get to the room.
+    pub direction: String,
+    pub room_id: ObjectId,
+    pub number: u32,
+    pub coords: Option<String>,
+}
+
+/// Finds the shortest route from the room with the ID `from` to the first
+/// room satisfying `is_goal`, taking only doors `user` can pass and rooms
+/// they have already entered. Returns the steps of the route, which is empty
+/// if the player is already there, or `None` if there is no such route.
+pub fn find_route<F>(
+    rooms: &Vec<Room>,
+    user: &User,
+    from: &ObjectId,
+    is_goal: F,
+) -> Option<Vec<RouteStep>>
+where
+    F: Fn(&Room) -> bool,
+{
+    let start: usize = rooms.iter().position(|room| &room.id == from)?;
+    let mut previous: Vec<Option<(usize, String)>> = vec![Option::default(); rooms.len()];
+    let mut visited: Vec<bool> = vec![false; rooms.len()];
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    visited[start] = true;
+    queue.push_back(start);
+    while let Some(idx) = queue.pop_front() {
+        if is_goal(&rooms[idx]) {
+            let mut route: Vec<RouteStep> = Vec::new();
+            let mut current: usize = idx;
+            while let Some((before, direction)) = &previous[current] {
+                route.push(RouteStep {
+                    direction: direction.clone(),
+                    room_id: rooms[current].id,
+                    number: rooms[current].number,
+                    coords: rooms[current].coords.clone(),
+                });
+                current = *before;
+            }
+            route.reverse();
+            return Some(route);
+        }
+        for neighbor in rooms[idx].neighbors.iter() {
+            if !neighbor.is_open_for(user) {
+                continue;
+            }
+            let behind: usize = match destination(rooms, &rooms[idx], neighbor) {
+                Some(behind) => behind,
+                None => continue,
+            };
+            if visited[behind] || !user.rooms_entered.contains(&rooms[behind].id) {
+                continue;
+            }
+            visited[behind] = true;
+            previous[behind] = Some((idx, neighbor.direction.clone()));
+            queue.push_back(behind);
+        }
+    }
+    Option::default()
+}
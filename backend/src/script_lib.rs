@@ -0,0 +1,243 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::i18n::accepted_languages;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rlua::{Context, Table, Value};
+use serde_json::{Map, Number};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+/// Tables nested deeper than this cannot be converted to JSON; this also
+/// catches tables that contain themselves.
+const MAX_JSON_DEPTH: usize = 32;
+
+/// What a script may know about the player it runs for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptPlayer {
+    pub username: String,
+    pub level: u32,
+    /// The language the player prefers, e.g. "de" or "en-US".
+    pub locale: Option<String>,
+}
+
+impl ScriptPlayer {
+    pub fn new(username: &String, level: u32, accept_language: &Option<String>) -> ScriptPlayer {
+        ScriptPlayer {
+            username: username.clone(),
+            level,
+            locale: accepted_languages(accept_language).into_iter().next(),
+        }
+    }
+}
+
+/// Shifts the letters A–Z and a–z by `shift` places in the alphabet; other
+/// characters are left alone.
+fn shift_letter(c: char, shift: i64) -> char {
+    let base: u8 = match c {
+        'a'..='z' => b'a',
+        'A'..='Z' => b'A',
+        _ => return c,
+    };
+    (base + ((c as u8 - base) as i64 + shift).rem_euclid(26) as u8) as char
+}
+
+fn caesar(text: &str, shift: i64) -> String {
+    text.chars().map(|c| shift_letter(c, shift)).collect()
+}
+
+/// Only letters of `text` consume letters of `key`, so spaces and
+/// punctuation are kept as they are.
+fn vigenere(text: &str, key: &str, decrypt: bool) -> String {
+    let shifts: Vec<i64> = key
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| (c.to_ascii_lowercase() as u8 - b'a') as i64)
+        .map(|shift| if decrypt { -shift } else { shift })
+        .collect();
+    if shifts.is_empty() {
+        return text.to_string();
+    }
+    let mut i: usize = 0;
+    text.chars()
+        .map(|c| match c.is_ascii_alphabetic() {
+            true => {
+                let shifted: char = shift_letter(c, shifts[i % shifts.len()]);
+                i += 1;
+                shifted
+            }
+            false => c,
+        })
+        .collect()
+}
+
+/// Converts a Lua value to JSON. Tables whose keys are exactly 1, 2, …, n
+/// become arrays, all other tables become objects.
+pub fn to_json(value: Value, depth: usize) -> rlua::Result<serde_json::Value> {
+    if depth > MAX_JSON_DEPTH {
+        return Err(rlua::Error::RuntimeError(
+            "table is nested too deeply to convert it to JSON".to_string(),
+        ));
+    }
+    Ok(match value {
+        Value::Nil => serde_json::Value::Null,
+        Value::Boolean(b) => serde_json::Value::Bool(b),
+        Value::Integer(i) => serde_json::Value::from(i),
+        Value::Number(n) => match Number::from_f64(n) {
+            Some(n) => serde_json::Value::Number(n),
+            None => serde_json::Value::Null,
+        },
+        Value::String(s) => {
+            serde_json::Value::String(String::from_utf8_lossy(s.as_bytes()).into_owned())
+        }
+        Value::Table(table) => {
+            let mut pairs: Vec<(Value, Value)> = Vec::new();
+            for pair in table.pairs::<Value, Value>() {
+                pairs.push(pair?);
+            }
+            let mut indices: Vec<i64> = pairs
+                .iter()
+                .filter_map(|(key, _)| match key {
+                    Value::Integer(i) => Some(*i),
+                    _ => Option::default(),
+                })
+                .collect();
+            indices.sort();
+            let is_array: bool = !pairs.is_empty()
+                && indices.len() == pairs.len()
+                && indices.iter().enumerate().all(|(n, i)| *i == n as i64 + 1);
+            match is_array {
+                true => {
+                    pairs.sort_by_key(|(key, _)| match key {
+                        Value::Integer(i) => *i,
+                        _ => 0,
+                    });
+                    let mut array: Vec<serde_json::Value> = Vec::new();
+                    for (_, value) in pairs.into_iter() {
+                        array.push(to_json(value, depth + 1)?);
+                    }
+                    serde_json::Value::Array(array)
+                }
+                false => {
+                    let mut object: Map<String, serde_json::Value> = Map::new();
+                    for (key, value) in pairs.into_iter() {
+                        let key: String = match key {
+                            Value::String(s) => String::from_utf8_lossy(s.as_bytes()).into_owned(),
+                            Value::Integer(i) => i.to_string(),
+                            Value::Number(n) => n.to_string(),
+                            Value::Boolean(b) => b.to_string(),
+                            _ => {
+                                return Err(rlua::Error::RuntimeError(
+                                    "only strings, numbers and booleans can be JSON keys"
+                                        .to_string(),
+                                ))
+                            }
+                        };
+                        object.insert(key, to_json(value, depth + 1)?);
+                    }
+                    serde_json::Value::Object(object)
+                }
+            }
+        }
+        _ => {
+            return Err(rlua::Error::RuntimeError(format!(
+                "a {} cannot be converted to JSON",
+                value.type_name()
+            )))
+        }
+    })
+}
+
+/// Turns a value returned by a script into bytes: strings are taken as they
+/// are, so they may hold binary data, tables are converted to JSON, numbers
+/// and booleans are written as text. Returns whether the value was a table.
+pub fn to_bytes(value: Value) -> rlua::Result<(Vec<u8>, bool)> {
+    match value {
+        Value::String(s) => Ok((s.as_bytes().to_vec(), false)),
+        Value::Integer(i) => Ok((i.to_string().into_bytes(), false)),
+        Value::Number(n) => Ok((n.to_string().into_bytes(), false)),
+        Value::Boolean(b) => Ok((b.to_string().into_bytes(), false)),
+        Value::Table(_) => Ok((to_json(value, 0)?.to_string().into_bytes(), true)),
+        value => Err(rlua::Error::RuntimeError(format!(
+            "expected a string or a table, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+/// Installs the global table `labyrinth` with helpers for riddle scripts.
+pub fn register(lua_ctx: Context, seed: i64, player: &ScriptPlayer) -> rlua::Result<()> {
+    let labyrinth: Table = lua_ctx.create_table()?;
+    labyrinth.set("seed", seed)?;
+    labyrinth.set("username", player.username.clone())?;
+    labyrinth.set("level", player.level)?;
+    labyrinth.set("locale", player.locale.clone())?;
+    let rng: Arc<Mutex<StdRng>> = Arc::new(Mutex::new(StdRng::seed_from_u64(seed as u64)));
+    labyrinth.set(
+        "random",
+        lua_ctx.create_function(move |_, (m, n): (Option<i64>, Option<i64>)| {
+            let mut rng = rng.lock().unwrap();
+            let (low, high): (i64, i64) = match (m, n) {
+                (None, _) => return Ok(Value::Number(rng.gen::<f64>())),
+                (Some(m), None) => (1, m),
+                (Some(m), Some(n)) => (m, n),
+            };
+            match low <= high {
+                true => Ok(Value::Integer(rng.gen_range(low..=high))),
+                false => Err(rlua::Error::RuntimeError(
+                    "random(): interval is empty".to_string(),
+                )),
+            }
+        })?,
+    )?;
+    labyrinth.set(
+        "base64_encode",
+        lua_ctx.create_function(|_, data: rlua::String| Ok(base64::encode(data.as_bytes())))?,
+    )?;
+    labyrinth.set(
+        "base64_decode",
+        lua_ctx.create_function(|lua_ctx, data: rlua::String| {
+            match base64::decode(data.as_bytes()) {
+                Ok(decoded) => lua_ctx.create_string(&decoded),
+                Err(e) => Err(rlua::Error::RuntimeError(format!("base64_decode(): {}", e))),
+            }
+        })?,
+    )?;
+    labyrinth.set(
+        "hex_encode",
+        lua_ctx.create_function(|_, data: rlua::String| Ok(hex::encode(data.as_bytes())))?,
+    )?;
+    labyrinth.set(
+        "hex_decode",
+        lua_ctx.create_function(|lua_ctx, data: rlua::String| {
+            match hex::decode(data.as_bytes()) {
+                Ok(decoded) => lua_ctx.create_string(&decoded),
+                Err(e) => Err(rlua::Error::RuntimeError(format!("hex_decode(): {}", e))),
+            }
+        })?,
+    )?;
+    labyrinth.set(
+        "sha256",
+        lua_ctx.create_function(|_, data: rlua::String| {
+            Ok(hex::encode(Sha256::digest(data.as_bytes())))
+        })?,
+    )?;
+    labyrinth.set(
+        "caesar",
+        lua_ctx.create_function(|_, (text, shift): (String, i64)| Ok(caesar(&text, shift)))?,
+    )?;
+    labyrinth.set(
+        "vigenere",
+        lua_ctx.create_function(|_, (text, key, decrypt): (String, String, Option<bool>)| {
+            Ok(vigenere(&text, &key, decrypt.unwrap_or(false)))
+        })?,
+    )?;
+    labyrinth.set(
+        "json",
+        lua_ctx.create_function(|_, value: Value| Ok(to_json(value, 0)?.to_string()))?,
+    )?;
+    lua_ctx.globals().set("labyrinth", labyrinth)?;
+    Ok(())
+}
@@ -1,26 +1,793 @@
-use rlua;
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::script_lib::{self, ScriptPlayer};
+use crate::wasm::{self, WasmEnv};
+use crate::{error::Error, Result};
+use bson::oid::ObjectId;
+use lazy_static::lazy_static;
+use log;
+use rlua::{FromLuaMulti, HookTriggers, StdLib, ToLuaMulti};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::convert::Infallible;
+use std::env;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use warp::Filter;
 
-pub struct ScriptEnv {
+/// Number of VM instructions between two checks of the instruction budget.
+const HOOK_INTERVAL: u32 = 1000;
+
+/// Functions of the base library that are removed from the sandbox. `dofile`
+/// and `loadfile` access the file system, `print` writes to the server's
+/// console, and `pcall` and `xpcall` could catch the errors raised when a
+/// script runs out of instructions or memory.
+const REMOVED_GLOBALS: [&str; 5] = ["dofile", "loadfile", "print", "pcall", "xpcall"];
+
+/// Name of the chunk the script is loaded as, so that error messages point
+/// to lines like `script:12:`.
+const CHUNK_NAME: &str = "=script";
+
+/// Functions a script must define, and those it may define.
+const REQUIRED_FUNCTIONS: [&str; 2] = ["task", "solution"];
+/// `solution()` may be left out if the script defines `verify()`.
+const OPTIONAL_FUNCTIONS: [&str; 6] =
+    ["name", "mime_type", "try", "verify", "items", "attachments"];
+
+/// Seeds a script is tried with before it is stored.
+const VALIDATION_SEEDS: [i64; 3] = [0, 1, 1_234_567_890];
+
+lazy_static! {
+    /// Number of Lua VM instructions a single script run may execute.
+    static ref SCRIPT_INSTRUCTION_LIMIT: u64 = env::var("SCRIPT_INSTRUCTION_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(10_000_000);
+    /// Number of bytes a script environment may allocate.
+    static ref SCRIPT_MEMORY_LIMIT: usize = env::var("SCRIPT_MEMORY_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(16 * 1024 * 1024);
+    /// Milliseconds a request waits for a script before giving up.
+    static ref SCRIPT_TIMEOUT: u64 = env::var("SCRIPT_TIMEOUT")
+        .ok()
+        .and_then(|timeout| timeout.parse().ok())
+        .unwrap_or(2000);
+    /// Number of script environments kept at most; the least recently used
+    /// one is dropped when another one is needed.
+    static ref SCRIPT_ENV_CAPACITY: usize = env::var("SCRIPT_ENV_CAPACITY")
+        .ok()
+        .and_then(|capacity| capacity.parse().ok())
+        .unwrap_or(1000);
+    /// Number of attachments a script may return.
+    static ref SCRIPT_ATTACHMENT_LIMIT: usize = env::var("SCRIPT_ATTACHMENT_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(16);
+    /// Number of bytes all attachments returned by a script may take up.
+    static ref SCRIPT_ATTACHMENT_BYTES: usize = env::var("SCRIPT_ATTACHMENT_BYTES")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(4 * 1024 * 1024);
+    /// Seconds after which an unused script environment is dropped.
+    static ref SCRIPT_ENV_TTL: u64 = env::var("SCRIPT_ENV_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(3600);
+}
+
+/// The language a riddle script is written in. WebAssembly modules are
+/// stored base64-encoded.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptEngine {
+    #[default]
+    Lua,
+    Wasm,
+}
+
+/// A sandboxed Lua state. Only the `base`, `table`, `string`, `utf8` and
+/// `math` libraries are available, minus the functions in `REMOVED_GLOBALS`,
+/// plus the `labyrinth` module from `script_lib`.
+pub struct LuaEnv {
     pub lua: rlua::Lua,
+    /// The riddle script has been executed successfully.
+    pub loaded: bool,
+    seed: i64,
+    player: ScriptPlayer,
+    instructions: Arc<AtomicU64>,
+    deadline: Arc<Mutex<Option<Instant>>>,
+}
+
+impl LuaEnv {
+    pub fn new(seed: i64, player: &ScriptPlayer) -> LuaEnv {
+        let lua: rlua::Lua = rlua::Lua::new_with(
+            StdLib::BASE | StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH,
+        );
+        lua.context(|lua_ctx| {
+            let globals = lua_ctx.globals();
+            for name in REMOVED_GLOBALS.iter() {
+                globals.set(*name, rlua::Nil)?;
+            }
+            // Scripts that seed the generator themselves must not break
+            // reproducibility, so `math.randomseed` does nothing afterwards.
+            let math: rlua::Table = globals.get("math")?;
+            let randomseed: rlua::Function = math.get("randomseed")?;
+            randomseed.call::<_, ()>(seed)?;
+            math.set("randomseed", lua_ctx.create_function(|_, ()| Ok(()))?)?;
+            script_lib::register(lua_ctx, seed, player)?;
+            Ok::<_, rlua::Error>(())
+        })
+        .expect("cannot set up Lua sandbox");
+        let instructions: Arc<AtomicU64> = Arc::new(AtomicU64::new(0));
+        let counter: Arc<AtomicU64> = instructions.clone();
+        let deadline: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(Option::default()));
+        let hook_deadline: Arc<Mutex<Option<Instant>>> = deadline.clone();
+        lua.set_hook(
+            HookTriggers {
+                every_nth_instruction: Some(HOOK_INTERVAL),
+                ..Default::default()
+            },
+            move |_, _| {
+                let executed: u64 = counter.fetch_add(HOOK_INTERVAL as u64, Ordering::Relaxed);
+                if executed >= *SCRIPT_INSTRUCTION_LIMIT {
+                    return Err(rlua::Error::RuntimeError(format!(
+                        "script exceeded the limit of {} instructions",
+                        *SCRIPT_INSTRUCTION_LIMIT
+                    )));
+                }
+                // Stops scripts whose caller has already given up on them.
+                match *hook_deadline.lock().unwrap() {
+                    Some(deadline) if Instant::now() > deadline => Err(rlua::Error::RuntimeError(
+                        format!("script exceeded the timeout of {} ms", *SCRIPT_TIMEOUT),
+                    )),
+                    _ => Ok(()),
+                }
+            },
+        );
+        lua.set_memory_limit(Some(lua.used_memory() + *SCRIPT_MEMORY_LIMIT));
+        LuaEnv {
+            lua,
+            loaded: false,
+            seed,
+            player: player.clone(),
+            instructions,
+            deadline,
+        }
+    }
+
+    /// Runs `f` with a fresh instruction budget, stopping it at `deadline`.
+    pub fn run<F, R>(&self, deadline: Instant, f: F) -> Result<R>
+    where
+        F: FnOnce(rlua::Context) -> rlua::Result<R>,
+    {
+        self.instructions.store(0, Ordering::Relaxed);
+        *self.deadline.lock().unwrap() = Some(deadline);
+        match self.lua.context(f) {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Error::ScriptError(describe(e))),
+        }
+    }
+
+    /// Executes `script` unless that has happened before, then calls its
+    /// functions. `guess` is passed to `try()`.
+    pub fn evaluate(
+        &mut self,
+        script: &str,
+        guess: Option<String>,
+        deadline: Instant,
+    ) -> Result<ScriptResult> {
+        if !self.loaded {
+            let loading: Result<()> = self.run(deadline, |lua_ctx| {
+                lua_ctx.load(script).set_name(CHUNK_NAME)?.exec()
+            });
+            if let Err(e) = loading {
+                // A half-executed script must not leave its traces behind.
+                *self = LuaEnv::new(self.seed, &self.player);
+                return Err(e);
+            }
+            self.loaded = true;
+        }
+        self.run(deadline, |lua_ctx| {
+            let globals = lua_ctx.globals();
+            let (task, structured) = script_lib::to_bytes(call_required(&globals, "task", ())?)?;
+            let mime_type: Option<String> = match call_optional(&globals, "mime_type", ())? {
+                Some(mime_type) => Some(mime_type),
+                None if structured => Some("application/json".to_string()),
+                None => Option::default(),
+            };
+            // Scripts that verify answers themselves need not have a single
+            // solution.
+            let solution: Option<String> = match globals.contains_key("verify")? {
+                true => call_optional(&globals, "solution", ())?,
+                false => Some(call_required(&globals, "solution", ())?),
+            };
+            let name: Option<String> = call_optional(&globals, "name", ())?;
+            // The feedback of `verify()` takes precedence over that of `try()`.
+            let (verdict, feedback) = match guess {
+                Some(guess) => match call_verify(&globals, guess.clone())? {
+                    Some((correct, Some(feedback))) => (Some(correct), Some(feedback)),
+                    Some((correct, None)) => {
+                        (Some(correct), call_optional(&globals, "try", guess)?)
+                    }
+                    None => (Option::default(), call_optional(&globals, "try", guess)?),
+                },
+                None => (Option::default(), Option::default()),
+            };
+            Ok(ScriptResult {
+                task: Some(task),
+                solution,
+                name,
+                mime_type,
+                verdict,
+                feedback,
+                // `items` names the items granted on solving the riddle.
+                items: call_optional(&globals, "items", ())?.unwrap_or_default(),
+                // `attachments` returns a list of tables with the fields
+                // `name`, `mime_type` and `data`.
+                attachments: match call_optional(&globals, "attachments", ())? {
+                    Some(attachments) => to_attachments(attachments)?,
+                    None => Vec::new(),
+                },
+            })
+        })
+    }
+
+    /// Executes `script` and checks that the functions it defines exist and
+    /// return values of the right types.
+    fn check(&self, script: &str) -> Result<()> {
+        self.run(deadline(), |lua_ctx| {
+            lua_ctx.load(script).set_name(CHUNK_NAME)?.exec()?;
+            let globals = lua_ctx.globals();
+            for name in REQUIRED_FUNCTIONS.iter().chain(OPTIONAL_FUNCTIONS.iter()) {
+                match globals.get::<_, rlua::Value>(*name)? {
+                    rlua::Value::Function(_) => (),
+                    rlua::Value::Nil if OPTIONAL_FUNCTIONS.contains(name) => (),
+                    rlua::Value::Nil
+                        if *name == "solution" && globals.contains_key("verify")? =>
+                    {
+                        ()
+                    }
+                    rlua::Value::Nil => return invalid(format!("function {}() is missing", name)),
+                    value => {
+                        return invalid(format!(
+                            "{} must be a function, not {}",
+                            name,
+                            value.type_name()
+                        ))
+                    }
+                }
+            }
+            match call_required(&globals, "task", ())? {
+                value @ (rlua::Value::String(_) | rlua::Value::Table(_)) => {
+                    if let Err(e) = script_lib::to_bytes(value) {
+                        return invalid(format!("task(): {}", describe(e)));
+                    }
+                }
+                value => {
+                    return invalid(format!(
+                        "task() must return a string or a table, not {}",
+                        value.type_name()
+                    ))
+                }
+            }
+            let solution: Option<String> = match call_optional(&globals, "solution", ())? {
+                None => Option::default(),
+                Some(rlua::Value::String(solution)) if solution.as_bytes().is_empty() => {
+                    return invalid("solution() must not return an empty string".to_string())
+                }
+                Some(rlua::Value::String(solution)) => {
+                    Some(String::from_utf8_lossy(solution.as_bytes()).into_owned())
+                }
+                Some(value) => {
+                    return invalid(format!(
+                        "solution() must return a string, not {}",
+                        value.type_name()
+                    ))
+                }
+            };
+            for name in ["name", "mime_type"].iter() {
+                match call_optional(&globals, name, ())? {
+                    None | Some(rlua::Value::String(_)) => (),
+                    Some(value) => {
+                        return invalid(format!(
+                            "{}() must return a string, not {}",
+                            name,
+                            value.type_name()
+                        ))
+                    }
+                }
+            }
+            if let Some(solution) = solution {
+                if let Some((false, _)) = call_verify(&globals, solution)? {
+                    return invalid("verify() rejects the result of solution()".to_string());
+                }
+            }
+            // A guess that is certainly wrong.
+            call_verify(&globals, String::new())?;
+            match call_optional(&globals, "try", "")? {
+                None | Some(rlua::Value::Nil) | Some(rlua::Value::String(_)) => (),
+                Some(value) => {
+                    return invalid(format!(
+                        "try() must return a string or nil, not {}",
+                        value.type_name()
+                    ))
+                }
+            }
+            call_optional::<_, Vec<String>>(&globals, "items", ())?;
+            if let Some(attachments) = call_optional(&globals, "attachments", ())? {
+                to_attachments(attachments)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// The environment a riddle script runs in, depending on its engine.
+pub enum ScriptEnv {
+    Lua(LuaEnv),
+    Wasm(WasmEnv),
 }
 
 impl ScriptEnv {
-    #[inline]
-    pub fn new() -> ScriptEnv {
-        ScriptEnv {
-            lua: rlua::Lua::new(),
+    pub fn new(engine: ScriptEngine, seed: i64, player: &ScriptPlayer) -> ScriptEnv {
+        match engine {
+            ScriptEngine::Lua => ScriptEnv::Lua(LuaEnv::new(seed, player)),
+            ScriptEngine::Wasm => ScriptEnv::Wasm(WasmEnv::new(seed, player)),
+        }
+    }
+
+    /// Runs the script, see `LuaEnv::evaluate` and `WasmEnv::evaluate`.
+    /// WebAssembly modules are stopped by running out of fuel rather than
+    /// by `deadline`.
+    pub fn evaluate(
+        &mut self,
+        script: &str,
+        guess: Option<String>,
+        deadline: Instant,
+    ) -> Result<ScriptResult> {
+        match self {
+            ScriptEnv::Lua(env) => env.evaluate(script, guess, deadline),
+            ScriptEnv::Wasm(env) => env.evaluate(script, guess),
+        }
+    }
+
+    fn check(&mut self, script: &str) -> Result<()> {
+        match self {
+            ScriptEnv::Lua(env) => env.check(script),
+            ScriptEnv::Wasm(env) => env.check(script),
+        }
+    }
+}
+
+fn invalid<T>(message: String) -> rlua::Result<T> {
+    Err(rlua::Error::RuntimeError(message))
+}
+
+/// Calls `verify(guess)` if the script defines it, which returns whether
+/// `guess` is correct and optionally some feedback. Anything but a boolean
+/// verdict is an error, so a faulty script never accepts an answer.
+fn call_verify<'lua>(
+    globals: &rlua::Table<'lua>,
+    guess: String,
+) -> rlua::Result<Option<(bool, Option<String>)>> {
+    match call_optional::<_, (rlua::Value, Option<String>)>(globals, "verify", guess)? {
+        Some((rlua::Value::Boolean(correct), feedback)) => Ok(Some((correct, feedback))),
+        Some((value, _)) => invalid(format!(
+            "verify() must return a boolean, not {}",
+            value.type_name()
+        )),
+        None => Ok(Option::default()),
+    }
+}
+
+/// Checks `script` before it is stored: it must compile, define `task()`
+/// and `solution()` or `verify()`, and all functions must return values of
+/// the right types when run with each of `VALIDATION_SEEDS`. All problems
+/// found are reported in one `Error::ScriptValidationError`.
+pub fn check_script(engine: ScriptEngine, script: &str) -> Result<()> {
+    let player = ScriptPlayer {
+        username: "validator".to_string(),
+        level: 0,
+        locale: Option::default(),
+    };
+    let compiled: Result<()> = match engine {
+        ScriptEngine::Lua => LuaEnv::new(0, &player).run(deadline(), |lua_ctx| {
+            lua_ctx.load(script).set_name(CHUNK_NAME)?.into_function()?;
+            Ok(())
+        }),
+        ScriptEngine::Wasm => wasm::compile(script).map(|_| ()),
+    };
+    if let Err(Error::ScriptError(message)) = compiled {
+        return Err(Error::ScriptValidationError(message));
+    }
+    let mut problems: Vec<String> = Vec::new();
+    for seed in VALIDATION_SEEDS.iter() {
+        let mut env: ScriptEnv = ScriptEnv::new(engine, *seed, &player);
+        if let Err(Error::ScriptError(message)) = env.check(script) {
+            // Most problems do not depend on the seed.
+            if !problems.iter().any(|problem| problem.ends_with(&message)) {
+                problems.push(format!("seed {}: {}", seed, message));
+            }
+        }
+    }
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(Error::ScriptValidationError(problems.join("; "))),
+    }
+}
+
+/// Runs `check_script` on a thread of the blocking pool.
+pub async fn validate_script(engine: ScriptEngine, script: &String) -> Result<()> {
+    let script: String = script.clone();
+    match tokio::task::spawn_blocking(move || check_script(engine, &script)).await {
+        Ok(result) => result,
+        Err(e) => Err(Error::ScriptError(e.to_string())),
+    }
+}
+
+/// The time by which a script started now has to finish.
+pub fn deadline() -> Instant {
+    Instant::now() + Duration::from_millis(*SCRIPT_TIMEOUT)
+}
+
+/// Turns a Lua error into a message the designer of the script can act on.
+fn describe(e: rlua::Error) -> String {
+    match e {
+        rlua::Error::SyntaxError { message, .. } => message,
+        // The stack trace points into the server rather than the script.
+        rlua::Error::RuntimeError(message) => match message.find("\nstack traceback:") {
+            Some(end) => message[..end].to_string(),
+            None => message,
+        },
+        rlua::Error::MemoryError(_) => format!(
+            "script exceeded the memory limit of {} bytes",
+            *SCRIPT_MEMORY_LIMIT
+        ),
+        rlua::Error::CallbackError { cause, .. } => describe((*cause).clone()),
+        e => e.to_string(),
+    }
+}
+
+struct ScriptSlot {
+    /// The seed of Lua's random number generator; together with the script
+    /// it determines the task and the solution.
+    seed: i64,
+    engine: ScriptEngine,
+    /// The hash of the script the environment has been set up for, so that
+    /// a changed script is run in a fresh environment.
+    script_hash: u64,
+    /// The player as the script sees them. The script reads the level and
+    /// locale only once, so a change calls for a fresh environment.
+    player: ScriptPlayer,
+    env: Arc<Mutex<ScriptEnv>>,
+    last_used: Instant,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ScriptEnvStats {
+    /// Number of environments currently kept.
+    pub live: usize,
+    pub capacity: usize,
+    pub ttl: u64,
+    /// Environments created since the start of the server, including
+    /// recreated ones.
+    pub created: u64,
+    /// Requests served by an existing environment.
+    pub reused: u64,
+    /// Environments dropped because they were idle for too long.
+    pub expired: u64,
+    /// Environments dropped to make room for others.
+    pub evicted: u64,
+}
+
+/// The script environments of the players, one per player and riddle. Each
+/// environment has its own lock, so a slow script only holds up further
+/// requests of the same player for the same riddle. Environments unused for
+/// `SCRIPT_ENV_TTL` seconds are dropped, as is the least recently used one
+/// if there are more than `SCRIPT_ENV_CAPACITY`. A dropped environment is
+/// recreated from the seed the next time it is needed.
+pub struct ScriptEnvMap {
+    slots: HashMap<(String, ObjectId), ScriptSlot>,
+    stats: ScriptEnvStats,
+}
+
+impl ScriptEnvMap {
+    pub fn new() -> ScriptEnvMap {
+        ScriptEnvMap {
+            slots: HashMap::new(),
+            stats: ScriptEnvStats::default(),
+        }
+    }
+
+    /// Returns the environment of `player` for the riddle `riddle_id`
+    /// seeded with `seed` and set up for `script`, creating it if necessary
+    /// or if the player's level or locale has changed.
+    fn get(
+        &mut self,
+        player: &ScriptPlayer,
+        riddle_id: &ObjectId,
+        seed: i64,
+        engine: ScriptEngine,
+        script: &str,
+    ) -> Arc<Mutex<ScriptEnv>> {
+        let key: (String, ObjectId) = (player.username.clone(), *riddle_id);
+        let now: Instant = Instant::now();
+        let script_hash: u64 = hash_script(script);
+        if let Some(slot) = self.slots.get_mut(&key) {
+            if slot.seed == seed
+                && slot.engine == engine
+                && slot.script_hash == script_hash
+                && slot.player == *player
+            {
+                slot.last_used = now;
+                self.stats.reused += 1;
+                return slot.env.clone();
+            }
+        }
+        self.expire();
+        if !self.slots.contains_key(&key) && self.slots.len() >= *SCRIPT_ENV_CAPACITY {
+            self.evict();
         }
+        log::info!(
+            "inserted {}/{} with seed {} into script_env",
+            player.username,
+            riddle_id,
+            seed
+        );
+        let env: Arc<Mutex<ScriptEnv>> = Arc::new(Mutex::new(ScriptEnv::new(engine, seed, player)));
+        self.slots.insert(
+            key,
+            ScriptSlot {
+                seed,
+                engine,
+                script_hash,
+                player: player.clone(),
+                env: env.clone(),
+                last_used: now,
+            },
+        );
+        self.stats.created += 1;
+        env
     }
+
+    /// Drops all environments unused for longer than `SCRIPT_ENV_TTL` seconds.
+    pub fn expire(&mut self) {
+        let ttl: Duration = Duration::from_secs(*SCRIPT_ENV_TTL);
+        let now: Instant = Instant::now();
+        let before: usize = self.slots.len();
+        self.slots
+            .retain(|_, slot| now.duration_since(slot.last_used) <= ttl);
+        let expired: usize = before - self.slots.len();
+        if expired > 0 {
+            log::info!("dropped {} idle script environments", expired);
+            self.stats.expired += expired as u64;
+        }
+    }
+
+    /// Drops the least recently used environment.
+    fn evict(&mut self) {
+        let oldest: Option<(String, ObjectId)> = self
+            .slots
+            .iter()
+            .min_by_key(|(_, slot)| slot.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            log::info!("evicted {}/{} from script_env", key.0, key.1);
+            self.slots.remove(&key);
+            self.stats.evicted += 1;
+        }
+    }
+
+    pub fn stats(&self) -> ScriptEnvStats {
+        ScriptEnvStats {
+            live: self.slots.len(),
+            capacity: *SCRIPT_ENV_CAPACITY,
+            ttl: *SCRIPT_ENV_TTL,
+            ..self.stats.clone()
+        }
+    }
+}
+
+fn hash_script(script: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    script.hash(&mut hasher);
+    hasher.finish()
 }
 
-pub type ScriptEnvMap = HashMap<String, ScriptEnv>;
+/// A new seed for a player's attempt at a scripted riddle.
+pub fn new_seed() -> i64 {
+    rand::random::<i64>()
+}
 
 pub fn with_script_env(
     env: Arc<Mutex<ScriptEnvMap>>,
 ) -> impl Filter<Extract = (Arc<Mutex<ScriptEnvMap>>,), Error = Infallible> + Clone {
     warp::any().map(move || env.clone())
 }
+
+/// A file generated by a script, e.g. an image or an archive.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl<'lua> rlua::FromLua<'lua> for Attachment {
+    fn from_lua(value: rlua::Value<'lua>, _: rlua::Context<'lua>) -> rlua::Result<Self> {
+        let table: rlua::Table = match value {
+            rlua::Value::Table(table) => table,
+            value => {
+                return Err(rlua::Error::RuntimeError(format!(
+                    "expected an attachment table, got {}",
+                    value.type_name()
+                )))
+            }
+        };
+        let name: String = match table.get::<_, Option<String>>("name")? {
+            Some(name) => name,
+            None => {
+                return Err(rlua::Error::RuntimeError(
+                    "attachment has no name".to_string(),
+                ))
+            }
+        };
+        let (data, structured) = script_lib::to_bytes(table.get("data")?)?;
+        Ok(Attachment {
+            name,
+            mime_type: match table.get::<_, Option<String>>("mime_type")? {
+                Some(mime_type) => mime_type,
+                None if structured => "application/json".to_string(),
+                None => "application/octet-stream".to_string(),
+            },
+            data,
+        })
+    }
+}
+
+/// Fails if a script returns more than `SCRIPT_ATTACHMENT_LIMIT`
+/// attachments.
+pub fn check_attachment_count(count: usize) -> Result<()> {
+    match count > *SCRIPT_ATTACHMENT_LIMIT {
+        true => Err(Error::ScriptError(format!(
+            "attachments(): more than {} attachments",
+            *SCRIPT_ATTACHMENT_LIMIT
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Fails if the attachments of a script take up more than
+/// `SCRIPT_ATTACHMENT_BYTES` bytes in total.
+pub fn check_attachment_size(size: usize) -> Result<()> {
+    match size > *SCRIPT_ATTACHMENT_BYTES {
+        true => Err(Error::ScriptError(format!(
+            "attachments(): attachments exceed {} bytes",
+            *SCRIPT_ATTACHMENT_BYTES
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Converts the list returned by `attachments()`, observing the limits on
+/// the number and size of attachments.
+fn to_attachments(table: rlua::Table) -> rlua::Result<Vec<Attachment>> {
+    let as_lua_error = |e: Error| match e {
+        Error::ScriptError(message) => rlua::Error::RuntimeError(message),
+        e => rlua::Error::RuntimeError(e.to_string()),
+    };
+    check_attachment_count(table.raw_len() as usize).map_err(as_lua_error)?;
+    let mut attachments: Vec<Attachment> = Vec::new();
+    let mut size: usize = 0;
+    for attachment in table.sequence_values::<Attachment>() {
+        let attachment: Attachment = attachment?;
+        size += attachment.data.len();
+        check_attachment_size(size).map_err(as_lua_error)?;
+        attachments.push(attachment);
+    }
+    Ok(attachments)
+}
+
+#[derive(Debug)]
+pub struct ScriptResult {
+    pub solution: Option<String>,
+    /// The task as returned by the script, i.e. possibly binary data.
+    pub task: Option<Vec<u8>>,
+    /// Whether `verify(guess)` accepted the guess, if the script defines it.
+    pub verdict: Option<bool>,
+    pub feedback: Option<String>,
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    pub items: Vec<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+/// Calls the global function `name` if the script defines it.
+fn call_optional<'lua, A, R>(
+    globals: &rlua::Table<'lua>,
+    name: &str,
+    args: A,
+) -> rlua::Result<Option<R>>
+where
+    A: ToLuaMulti<'lua>,
+    R: FromLuaMulti<'lua>,
+{
+    match globals.get::<_, Option<rlua::Function>>(name)? {
+        Some(f) => match f.call::<A, R>(args) {
+            Ok(result) => Ok(Some(result)),
+            Err(e) => Err(rlua::Error::RuntimeError(format!(
+                "{}(): {}",
+                name,
+                describe(e)
+            ))),
+        },
+        None => Ok(Option::default()),
+    }
+}
+
+/// Calls the global function `name`, which every script must define.
+fn call_required<'lua, A, R>(globals: &rlua::Table<'lua>, name: &str, args: A) -> rlua::Result<R>
+where
+    A: ToLuaMulti<'lua>,
+    R: FromLuaMulti<'lua>,
+{
+    match call_optional(globals, name, args)? {
+        Some(result) => Ok(result),
+        None => Err(rlua::Error::RuntimeError(format!(
+            "function {}() is missing",
+            name
+        ))),
+    }
+}
+
+/// Runs the riddle `script`, written for `engine`, in the environment of
+/// `player` for the riddle `riddle_id`. The script must define `task()` and
+/// `solution()` or `verify(guess)`; `name()`, `mime_type()`, `try(guess)`,
+/// `items()` and `attachments()` are optional. `task()` may return binary
+/// data in a string or a table, which is converted to JSON. If there is no
+/// environment seeded with `seed` yet, e.g. after a restart or because the
+/// script has changed, a fresh one is created and the script is executed in
+/// it, so the same seed always yields the same task and solution. The script
+/// runs on a thread of the blocking pool; if it does not finish within
+/// `SCRIPT_TIMEOUT` milliseconds of starting, `Error::ScriptTimeoutError` is
+/// returned.
+pub async fn evaluate_script(
+    player: &ScriptPlayer,
+    riddle_id: &ObjectId,
+    seed: i64,
+    engine: ScriptEngine,
+    script: &String,
+    guess: Option<String>,
+    env: Arc<Mutex<ScriptEnvMap>>,
+) -> Result<ScriptResult> {
+    let user_env: Arc<Mutex<ScriptEnv>> = env
+        .lock()
+        .unwrap()
+        .get(player, riddle_id, seed, engine, script);
+    let timeout: Duration = Duration::from_millis(*SCRIPT_TIMEOUT);
+    let script: String = script.clone();
+    let (started, has_started) = tokio::sync::oneshot::channel::<()>();
+    let evaluation = tokio::task::spawn_blocking(move || {
+        let mut user_env = user_env.lock().unwrap();
+        started.send(()).ok();
+        user_env.evaluate(&script, guess, deadline())
+    });
+    // Waiting for earlier runs in the same environment, each limited by the
+    // timeout itself, does not count against the timeout of this one.
+    has_started.await.ok();
+    let result: Result<ScriptResult> = match tokio::time::timeout(timeout, evaluation).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => Err(Error::ScriptError(e.to_string())),
+        Err(_) => Err(Error::ScriptTimeoutError),
+    };
+    if let Err(e) = &result {
+        log::warn!("script of {} failed: {}", player.username, e);
+    }
+    result
+}
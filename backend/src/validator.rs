@@ -0,0 +1,317 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::db::{riddle_ids_of_rooms, Direction, DoorType, Game, Riddle, Room, DB};
+use crate::Result;
+use bson::oid::ObjectId;
+use log;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    UnknownDirection,
+    OneSidedDoor,
+    MissingTarget,
+    MissingRiddle,
+    MissingItem,
+    DuplicateRiddle,
+    LevelMismatch,
+    MissingEntry,
+    MissingExit,
+    UnreachableRoom,
+    UnreachableExit,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ValidationIssue {
+    pub game_id: ObjectId,
+    pub kind: IssueKind,
+    pub room_id: Option<ObjectId>,
+    pub riddle_id: Option<ObjectId>,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(
+        game_id: &ObjectId,
+        kind: IssueKind,
+        room: Option<&Room>,
+        riddle_id: Option<ObjectId>,
+        message: String,
+    ) -> ValidationIssue {
+        ValidationIssue {
+            game_id: *game_id,
+            kind,
+            room_id: room.map(|room| room.id),
+            riddle_id,
+            message,
+        }
+    }
+}
+
+/// Finds the index of the room on the other side of `direction`, i.e. a
+/// different room with a door named like the counterpart of `direction`
+/// and guarded by the same riddle. This mirrors the lookup `go_handler`
+/// does via `DB::get_room_behind`.
+fn room_behind(rooms: &Vec<Room>, room: &Room, direction: &Direction) -> Option<usize> {
+    let counterpart: String = match direction.counterpart() {
+        Some(counterpart) => counterpart,
+        None => return Option::default(),
+    };
+    let riddle_id: ObjectId = match direction.riddle_id {
+        Some(riddle_id) => riddle_id,
+        None => return Option::default(),
+    };
+    rooms.iter().position(|other| {
+        other.id != room.id
+            && other
+                .neighbors
+                .iter()
+                .any(|n| n.direction == counterpart && n.riddle_id == Some(riddle_id))
+    })
+}
+
+/// Finds the index of the room `direction` leads to, depending on its type.
+pub fn destination(rooms: &Vec<Room>, room: &Room, direction: &Direction) -> Option<usize> {
+    match (&direction.door, &direction.target) {
+        (_, Some(target)) => rooms.iter().position(|other| &other.id == target),
+        (DoorType::Regular, None) => room_behind(rooms, room, direction),
+        (_, None) => Option::default(),
+    }
+}
+
+pub fn validate_game(
+    game: &Game,
+    rooms: &Vec<Room>,
+    riddles: &Vec<Riddle>,
+) -> Vec<ValidationIssue> {
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+    let riddle_levels: HashMap<ObjectId, u32> = riddles
+        .iter()
+        .map(|riddle| (riddle.id, riddle.level))
+        .collect();
+    // Every door has two sides, so each riddle is expected to be referenced
+    // at most twice.
+    let mut usage: BTreeMap<ObjectId, u32> = BTreeMap::new();
+    for room in rooms.iter() {
+        for neighbor in room.neighbors.iter() {
+            if let Some(riddle_id) = neighbor.riddle_id {
+                *usage.entry(riddle_id).or_insert(0) += 1;
+                match riddle_levels.get(&riddle_id) {
+                    Some(level) if *level != neighbor.level => issues.push(ValidationIssue::new(
+                        &game.id,
+                        IssueKind::LevelMismatch,
+                        Some(room),
+                        Some(riddle_id),
+                        format!(
+                            "door '{}' of room {} has level {}, but its riddle has level {}",
+                            neighbor.direction, room.number, neighbor.level, level
+                        ),
+                    )),
+                    Some(_) => (),
+                    None => issues.push(ValidationIssue::new(
+                        &game.id,
+                        IssueKind::MissingRiddle,
+                        Some(room),
+                        Some(riddle_id),
+                        format!(
+                            "door '{}' of room {} references a riddle that does not exist",
+                            neighbor.direction, room.number
+                        ),
+                    )),
+                }
+            }
+            if let Some(condition) = &neighbor.condition {
+                for riddle_id in condition.riddles.iter() {
+                    if !riddle_levels.contains_key(riddle_id) {
+                        issues.push(ValidationIssue::new(
+                            &game.id,
+                            IssueKind::MissingRiddle,
+                            Some(room),
+                            Some(*riddle_id),
+                            format!(
+                                "door '{}' of room {} requires a riddle that guards no door of this game",
+                                neighbor.direction, room.number
+                            ),
+                        ));
+                    }
+                }
+            }
+            if let Some(item) = &neighbor.item {
+                // Items may also be granted by riddle scripts, which cannot be
+                // checked here, so only doors of games without scripts count.
+                if !rooms
+                    .iter()
+                    .any(|room| room.items.iter().any(|other| other.is_named(item)))
+                    && !riddles.iter().any(|riddle| riddle.script.is_some())
+                {
+                    issues.push(ValidationIssue::new(
+                        &game.id,
+                        IssueKind::MissingItem,
+                        Some(room),
+                        neighbor.riddle_id,
+                        format!(
+                            "door '{}' of room {} requires item '{}', which is placed in no room",
+                            neighbor.direction, room.number, item
+                        ),
+                    ));
+                }
+            }
+            match (&neighbor.door, &neighbor.target, &neighbor.riddle_id) {
+                (_, Some(_), _) => {
+                    let inside: bool = match destination(rooms, room, neighbor) {
+                        Some(idx) => rooms[idx].game_id == room.game_id,
+                        None => false,
+                    };
+                    if !inside {
+                        issues.push(ValidationIssue::new(
+                            &game.id,
+                            IssueKind::MissingTarget,
+                            Some(room),
+                            neighbor.riddle_id,
+                            format!(
+                                "door '{}' of room {} leads to a room outside of this game",
+                                neighbor.direction, room.number
+                            ),
+                        ));
+                    }
+                }
+                (DoorType::Regular, None, Some(_)) => {
+                    if neighbor.counterpart().is_none() {
+                        issues.push(ValidationIssue::new(
+                            &game.id,
+                            IssueKind::UnknownDirection,
+                            Some(room),
+                            neighbor.riddle_id,
+                            format!(
+                                "door '{}' of room {} declares no counterpart",
+                                neighbor.direction, room.number
+                            ),
+                        ));
+                    } else if room_behind(rooms, room, neighbor).is_none() {
+                        issues.push(ValidationIssue::new(
+                            &game.id,
+                            IssueKind::OneSidedDoor,
+                            Some(room),
+                            neighbor.riddle_id,
+                            format!(
+                                "door '{}' of room {} leads nowhere",
+                                neighbor.direction, room.number
+                            ),
+                        ));
+                    }
+                }
+                _ => issues.push(ValidationIssue::new(
+                    &game.id,
+                    IssueKind::MissingTarget,
+                    Some(room),
+                    neighbor.riddle_id,
+                    format!(
+                        "door '{}' of room {} needs a target",
+                        neighbor.direction, room.number
+                    ),
+                )),
+            }
+        }
+    }
+    for (riddle_id, count) in usage.iter() {
+        if *count > 2 {
+            issues.push(ValidationIssue::new(
+                &game.id,
+                IssueKind::DuplicateRiddle,
+                Option::default(),
+                Some(*riddle_id),
+                format!("riddle is used by {} door sides", count),
+            ));
+        }
+    }
+    let entries: Vec<usize> = rooms
+        .iter()
+        .enumerate()
+        .filter(|(_, room)| room.entry.unwrap_or(false))
+        .map(|(idx, _)| idx)
+        .collect();
+    if entries.is_empty() {
+        issues.push(ValidationIssue::new(
+            &game.id,
+            IssueKind::MissingEntry,
+            Option::default(),
+            Option::default(),
+            "game has no entry".to_string(),
+        ));
+    }
+    if !rooms.iter().any(|room| room.exit.unwrap_or(false)) {
+        issues.push(ValidationIssue::new(
+            &game.id,
+            IssueKind::MissingExit,
+            Option::default(),
+            Option::default(),
+            "game has no exit".to_string(),
+        ));
+    }
+    if entries.is_empty() {
+        return issues;
+    }
+    let mut visited: Vec<bool> = vec![false; rooms.len()];
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    for idx in entries {
+        visited[idx] = true;
+        queue.push_back(idx);
+    }
+    while let Some(idx) = queue.pop_front() {
+        for neighbor in rooms[idx].neighbors.iter() {
+            if let Some(behind) = destination(rooms, &rooms[idx], neighbor) {
+                if !visited[behind] {
+                    visited[behind] = true;
+                    queue.push_back(behind);
+                }
+            }
+        }
+    }
+    for (idx, room) in rooms.iter().enumerate() {
+        if visited[idx] {
+            continue;
+        }
+        match room.exit.unwrap_or(false) {
+            true => issues.push(ValidationIssue::new(
+                &game.id,
+                IssueKind::UnreachableExit,
+                Some(room),
+                Option::default(),
+                format!("exit room {} cannot be reached from the entry", room.number),
+            )),
+            false => issues.push(ValidationIssue::new(
+                &game.id,
+                IssueKind::UnreachableRoom,
+                Some(room),
+                Option::default(),
+                format!("room {} cannot be reached from the entry", room.number),
+            )),
+        }
+    }
+    issues
+}
+
+pub async fn validate_all(db: &DB) -> Result<Vec<ValidationIssue>> {
+    let games: Vec<Game> = match db.get_games().await {
+        Ok(games) => games,
+        Err(e) => return Err(e),
+    };
+    let mut issues: Vec<ValidationIssue> = Vec::new();
+    for game in games.iter() {
+        log::info!("validating game {} ({})", &game.name, &game.id);
+        let rooms: Vec<Room> = match db.get_rooms_of_game(&game.id).await {
+            Ok(rooms) => rooms,
+            Err(e) => return Err(e),
+        };
+        let riddle_ids: Vec<ObjectId> = riddle_ids_of_rooms(&rooms);
+        let riddles: Vec<Riddle> = match db.get_riddles_by_oids(&riddle_ids).await {
+            Ok(riddles) => riddles,
+            Err(e) => return Err(e),
+        };
+        issues.append(&mut validate_game(game, &rooms, &riddles));
+    }
+    Ok(issues)
+}
@@ -0,0 +1,409 @@
+/**
+ * Copyright (c) 2022 Oliver Lau <oliver@ersatzworld.net>
+ * All rights reserved.
+ */
+use crate::script_lib::ScriptPlayer;
+use crate::scripting::{check_attachment_count, check_attachment_size, Attachment, ScriptResult};
+use crate::{error::Error, Result};
+use lazy_static::lazy_static;
+use std::env;
+use wasmi::core::TrapCode;
+use wasmi::{
+    Caller, Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder, TypedFunc, WasmParams, WasmResults,
+};
+
+lazy_static! {
+    /// Units of fuel a single run of a module may consume; most
+    /// instructions cost one unit.
+    static ref WASM_FUEL_LIMIT: u64 = env::var("WASM_FUEL_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(10_000_000);
+    /// Number of bytes the linear memory of a module may grow to.
+    static ref WASM_MEMORY_LIMIT: usize = env::var("WASM_MEMORY_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(16 * 1024 * 1024);
+    /// Shared by all modules. Fuel metering has to be enabled here.
+    static ref ENGINE: Engine = {
+        let mut config: Config = Config::default();
+        config.consume_fuel(true);
+        Engine::new(&config)
+    };
+}
+
+/// What the host functions of a module may access.
+pub struct WasmState {
+    seed: i64,
+    level: u32,
+    /// Set by `labyrinth.feedback()` while `verify()` runs.
+    feedback: Option<String>,
+    limits: StoreLimits,
+}
+
+/// An instance of a riddle module, the WebAssembly counterpart of a Lua
+/// script environment. The module is instantiated on first use.
+///
+/// A module exports its `memory` and these functions, which return byte
+/// strings as `ptr << 32 | len`:
+///
+/// - `task() -> i64` and `solution() -> i64`, the latter being optional if
+///   the module exports `verify`,
+/// - optionally `name() -> i64`, `mime_type() -> i64` and `items() -> i64`,
+///   the latter returning one item name per line,
+/// - optionally `verify(ptr: i32, len: i32) -> i32`, which returns 1 if the
+///   guess at `ptr` is correct and 0 otherwise,
+/// - optionally `try(ptr: i32, len: i32) -> i64` giving feedback on a guess,
+/// - `alloc(len: i32) -> i32` if it exports `verify` or `try`, so that the
+///   guess can be copied into the module's memory,
+/// - optionally `attachment_count() -> i32` along with
+///   `attachment_name(i: i32) -> i64`, `attachment_data(i: i32) -> i64` and
+///   optionally `attachment_mime_type(i: i32) -> i64`.
+///
+/// A module may import `labyrinth.seed() -> i64`, `labyrinth.level() -> i32`
+/// and `labyrinth.feedback(ptr: i32, len: i32)`, with which `verify` can give
+/// feedback.
+pub struct WasmEnv {
+    store: Store<WasmState>,
+    instance: Option<Instance>,
+}
+
+fn new_store(seed: i64, level: u32) -> Store<WasmState> {
+    let mut store: Store<WasmState> = Store::new(
+        &ENGINE,
+        WasmState {
+            seed,
+            level,
+            feedback: Option::default(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(*WASM_MEMORY_LIMIT)
+                .instances(1)
+                .build(),
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    store
+}
+
+fn script_error<T>(message: String) -> Result<T> {
+    Err(Error::ScriptError(message))
+}
+
+/// Turns a WebAssembly error into a message the author of the module can
+/// act on.
+fn describe(e: wasmi::Error) -> String {
+    match e.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => format!(
+            "module exceeded the limit of {} units of fuel",
+            *WASM_FUEL_LIMIT
+        ),
+        _ => e.to_string(),
+    }
+}
+
+/// Decodes and compiles the base64-encoded module `script`.
+pub fn compile(script: &str) -> Result<Module> {
+    let wasm: Vec<u8> = match base64::decode(script.trim()) {
+        Ok(wasm) => wasm,
+        Err(e) => return script_error(format!("module is not base64-encoded: {}", e)),
+    };
+    match Module::new(&ENGINE, &wasm) {
+        Ok(module) => Ok(module),
+        Err(e) => script_error(format!("invalid module: {}", describe(e))),
+    }
+}
+
+impl WasmEnv {
+    pub fn new(seed: i64, player: &ScriptPlayer) -> WasmEnv {
+        WasmEnv {
+            store: new_store(seed, player.level),
+            instance: Option::default(),
+        }
+    }
+
+    fn instantiate(&mut self, script: &str) -> Result<Instance> {
+        let module: Module = compile(script)?;
+        let mut linker: Linker<WasmState> = Linker::new(&ENGINE);
+        let linked = linker
+            .func_wrap("labyrinth", "seed", |caller: Caller<WasmState>| -> i64 {
+                caller.data().seed
+            })
+            .and_then(|linker| {
+                linker.func_wrap("labyrinth", "level", |caller: Caller<WasmState>| -> i32 {
+                    caller.data().level as i32
+                })
+            })
+            .and_then(|linker| {
+                linker.func_wrap(
+                    "labyrinth",
+                    "feedback",
+                    |mut caller: Caller<WasmState>, ptr: i32, len: i32| {
+                        let memory: Option<Memory> =
+                            caller.get_export("memory").and_then(|e| e.into_memory());
+                        let mut feedback: Vec<u8> = vec![0; (len as u32 as usize).min(4096)];
+                        if let Some(memory) = memory {
+                            if memory
+                                .read(&caller, ptr as u32 as usize, &mut feedback)
+                                .is_ok()
+                            {
+                                caller.data_mut().feedback =
+                                    Some(String::from_utf8_lossy(&feedback).into_owned());
+                            }
+                        }
+                    },
+                )
+            });
+        if let Err(e) = linked {
+            return script_error(e.to_string());
+        }
+        self.store.set_fuel(*WASM_FUEL_LIMIT).ok();
+        match linker
+            .instantiate(&mut self.store, &module)
+            .and_then(|instance| instance.start(&mut self.store))
+        {
+            Ok(instance) => Ok(instance),
+            Err(e) => script_error(describe(e)),
+        }
+    }
+
+    fn instance(&self) -> Instance {
+        self.instance.expect("module has not been instantiated")
+    }
+
+    /// Looks up the exported function `name`, which must have the signature
+    /// `P -> R` if it exists.
+    fn func<P, R>(&self, name: &str) -> Result<Option<TypedFunc<P, R>>>
+    where
+        P: WasmParams,
+        R: WasmResults,
+    {
+        let instance: Instance = self.instance();
+        if instance.get_export(&self.store, name).is_none() {
+            return Ok(Option::default());
+        }
+        match instance.get_typed_func::<P, R>(&self.store, name) {
+            Ok(f) => Ok(Some(f)),
+            Err(e) => script_error(format!("{}() has the wrong signature: {}", name, e)),
+        }
+    }
+
+    /// Calls the exported function `name` if the module defines it.
+    fn call_optional<P, R>(&mut self, name: &str, params: P) -> Result<Option<R>>
+    where
+        P: WasmParams,
+        R: WasmResults,
+    {
+        match self.func::<P, R>(name)? {
+            Some(f) => match f.call(&mut self.store, params) {
+                Ok(result) => Ok(Some(result)),
+                Err(e) => script_error(format!("{}(): {}", name, describe(e))),
+            },
+            None => Ok(Option::default()),
+        }
+    }
+
+    /// Calls the exported function `name`, which every module must define.
+    fn call_required<P, R>(&mut self, name: &str, params: P) -> Result<R>
+    where
+        P: WasmParams,
+        R: WasmResults,
+    {
+        match self.call_optional(name, params)? {
+            Some(result) => Ok(result),
+            None => script_error(format!("function {}() is missing", name)),
+        }
+    }
+
+    fn memory(&self) -> Result<Memory> {
+        match self.instance().get_memory(&self.store, "memory") {
+            Some(memory) => Ok(memory),
+            None => script_error("module does not export its memory".to_string()),
+        }
+    }
+
+    /// Reads the byte string returned by `name` as `ptr << 32 | len`.
+    fn read(&self, name: &str, packed: i64) -> Result<Vec<u8>> {
+        let ptr: usize = (packed as u64 >> 32) as usize;
+        let len: usize = (packed as u64 & 0xffff_ffff) as usize;
+        let memory: Memory = self.memory()?;
+        if ptr + len > memory.data(&self.store).len() {
+            return script_error(format!("{}() returned data outside of memory", name));
+        }
+        let mut data: Vec<u8> = vec![0; len];
+        match memory.read(&self.store, ptr, &mut data) {
+            Ok(()) => Ok(data),
+            Err(e) => script_error(format!("{}(): {}", name, e)),
+        }
+    }
+
+    fn bytes(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        match self.call_optional::<(), i64>(name, ())? {
+            Some(packed) => Ok(Some(self.read(name, packed)?)),
+            None => Ok(Option::default()),
+        }
+    }
+
+    fn text(&mut self, name: &str) -> Result<Option<String>> {
+        match self.bytes(name)? {
+            Some(bytes) => match String::from_utf8(bytes) {
+                Ok(text) => Ok(Some(text)),
+                Err(_) => script_error(format!("{}() must return UTF-8 text", name)),
+            },
+            None => Ok(Option::default()),
+        }
+    }
+
+    /// Copies `guess` into memory allocated by the module's `alloc()`.
+    fn write_guess(&mut self, guess: &str) -> Result<(i32, i32)> {
+        let len: i32 = guess.len() as i32;
+        let ptr: i32 = self.call_required::<i32, i32>("alloc", len)?;
+        let memory: Memory = self.memory()?;
+        match memory.write(&mut self.store, ptr as u32 as usize, guess.as_bytes()) {
+            Ok(()) => Ok((ptr, len)),
+            Err(e) => script_error(format!("alloc(): {}", e)),
+        }
+    }
+
+    /// Calls `verify()` with `guess` if the module exports it. Anything but 0
+    /// or 1 is an error, so a faulty module never accepts an answer.
+    fn verify(&mut self, guess: &str) -> Result<Option<(bool, Option<String>)>> {
+        if self.func::<(i32, i32), i32>("verify")?.is_none() {
+            return Ok(Option::default());
+        }
+        let params: (i32, i32) = self.write_guess(guess)?;
+        self.store.data_mut().feedback = Option::default();
+        let correct: bool = match self.call_required::<(i32, i32), i32>("verify", params)? {
+            0 => false,
+            1 => true,
+            verdict => {
+                return script_error(format!("verify() must return 0 or 1, not {}", verdict))
+            }
+        };
+        Ok(Some((correct, self.store.data_mut().feedback.take())))
+    }
+
+    fn try_guess(&mut self, guess: &str) -> Result<Option<String>> {
+        if self.func::<(i32, i32), i64>("try")?.is_none() {
+            return Ok(Option::default());
+        }
+        let params: (i32, i32) = self.write_guess(guess)?;
+        let packed: i64 = self.call_required::<(i32, i32), i64>("try", params)?;
+        match String::from_utf8(self.read("try", packed)?) {
+            Ok(feedback) => Ok(Some(feedback)),
+            Err(_) => script_error("try() must return UTF-8 text".to_string()),
+        }
+    }
+
+    /// Copies the attachments out of the module's memory, observing the
+    /// limits on their number and total size before copying anything.
+    fn attachments(&mut self) -> Result<Vec<Attachment>> {
+        let count: i32 = self
+            .call_optional::<(), i32>("attachment_count", ())?
+            .unwrap_or(0);
+        check_attachment_count(count.max(0) as usize)?;
+        let mut attachments: Vec<Attachment> = Vec::new();
+        let mut size: usize = 0;
+        for i in 0..count {
+            let packed: i64 = self.call_required::<i32, i64>("attachment_name", i)?;
+            let name: String = match String::from_utf8(self.read("attachment_name", packed)?) {
+                Ok(name) => name,
+                Err(_) => {
+                    return script_error("attachment_name() must return UTF-8 text".to_string())
+                }
+            };
+            let mime_type: String =
+                match self.call_optional::<i32, i64>("attachment_mime_type", i)? {
+                    Some(packed) => {
+                        String::from_utf8_lossy(&self.read("attachment_mime_type", packed)?)
+                            .into_owned()
+                    }
+                    None => "application/octet-stream".to_string(),
+                };
+            let packed: i64 = self.call_required::<i32, i64>("attachment_data", i)?;
+            size += (packed as u64 & 0xffff_ffff) as usize;
+            check_attachment_size(size)?;
+            attachments.push(Attachment {
+                name,
+                mime_type,
+                data: self.read("attachment_data", packed)?,
+            });
+        }
+        Ok(attachments)
+    }
+
+    /// Instantiates the module `script` unless that has happened before,
+    /// then calls its functions with a fresh supply of fuel. `guess` is
+    /// passed to `verify()` and `try()`.
+    pub fn evaluate(&mut self, script: &str, guess: Option<String>) -> Result<ScriptResult> {
+        if self.instance.is_none() {
+            match self.instantiate(script) {
+                Ok(instance) => self.instance = Some(instance),
+                Err(e) => {
+                    // A half-initialized module must not leave its traces behind.
+                    let state: &WasmState = self.store.data();
+                    self.store = new_store(state.seed, state.level);
+                    return Err(e);
+                }
+            }
+        }
+        self.store.set_fuel(*WASM_FUEL_LIMIT).ok();
+        let task: Vec<u8> = match self.bytes("task")? {
+            Some(task) => task,
+            None => return script_error("function task() is missing".to_string()),
+        };
+        let mime_type: Option<String> = self.text("mime_type")?;
+        // Modules that verify answers themselves need not have a single
+        // solution.
+        let solution: Option<String> = match self.func::<(i32, i32), i32>("verify")? {
+            Some(_) => self.text("solution")?,
+            None => match self.text("solution")? {
+                Some(solution) => Some(solution),
+                None => return script_error("function solution() is missing".to_string()),
+            },
+        };
+        let name: Option<String> = self.text("name")?;
+        let (verdict, feedback) = match guess {
+            Some(guess) => match self.verify(&guess)? {
+                Some((correct, Some(feedback))) => (Some(correct), Some(feedback)),
+                Some((correct, None)) => (Some(correct), self.try_guess(&guess)?),
+                None => (Option::default(), self.try_guess(&guess)?),
+            },
+            None => (Option::default(), Option::default()),
+        };
+        let items: Vec<String> = match self.text("items")? {
+            Some(items) => items
+                .lines()
+                .filter(|item| !item.trim().is_empty())
+                .map(|item| item.trim().to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(ScriptResult {
+            task: Some(task),
+            solution,
+            name,
+            mime_type,
+            verdict,
+            feedback,
+            items,
+            attachments: self.attachments()?,
+        })
+    }
+
+    /// Instantiates the module `script` and checks that its exports have the
+    /// right signatures and return values of the right types.
+    pub fn check(&mut self, script: &str) -> Result<()> {
+        // An empty guess exercises `verify()` and `try()` as well.
+        let result: ScriptResult = self.evaluate(script, Some(String::new()))?;
+        if let Some(solution) = result.solution {
+            if solution.is_empty() {
+                return script_error("solution() must not return an empty string".to_string());
+            }
+            if let Some((false, _)) = self.verify(&solution)? {
+                return script_error("verify() rejects the result of solution()".to_string());
+            }
+        }
+        Ok(())
+    }
+}
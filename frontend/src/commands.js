@@ -4,8 +4,24 @@ const CMDNAMES = {
     EAST: 'east',
     SOUTH: 'south',
     WEST: 'west',
+    UP: 'up',
+    DOWN: 'down',
+    GO: 'go',
     WHOAMI: 'whoami',
     WHEREAMI: 'whereami',
+    LOOK: 'look',
+    EXAMINE: 'examine',
+    TAKE: 'take',
+    DROP: 'drop',
+    INVENTORY: 'inventory',
+    MAP: 'map',
+    TRAVEL: 'travel',
+    ROUTE: 'route',
+    SAY: 'say',
+    TEAM: 'team',
+    NOTE: 'note',
+    REPORT: 'report',
+    RATE: 'rate',
     CLEAR: 'clear',
     PING: 'ping',
     CHEAT: 'cheat',
@@ -96,7 +112,7 @@ const COMMANDS = [
                                 // fall-through
                             case 'image/webp':
                                 const img = document.createElement('img');
-                                img.src = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                                img.src = fileURL(f);
                                 if (f.variants) {
                                     img.srcset = f.variants.map(v => `${UPLOAD_FOLDER}/${v.uploadedName} ${v.scale}x`).join(' ');
                                 }
@@ -107,14 +123,14 @@ const COMMANDS = [
                             case 'image/svg':
                             case 'image/svg+xml':
                                 let svgimg = document.createElement('img');
-                                svgimg.src = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                                svgimg.src = fileURL(f);
                                 this.term.container.appendChild(svgimg);
                                 this.term.container.appendChild(makeDownloadLink(f));
                                 break;
                             case 'audio/mp3':
                                 // fall-through
                             case 'audio/flac':
-                                const dataUrl = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                                const dataUrl = fileURL(f);
                                 this.print(`<audio controls><source src="${dataUrl}" type="${f.mimeType}"></audio>`);
                                 break;
                             case 'text/markdown':
@@ -127,7 +143,7 @@ const COMMANDS = [
                                 break;
                             case 'text/html':
                                 const embed = document.createElement('embed');
-                                embed.src = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                                embed.src = fileURL(f);
                                 embed.type = f.mimeType;
                                 this.term.container.appendChild(embed);
                                 this.term.container.appendChild(makeDownloadLink(f));
@@ -141,7 +157,7 @@ const COMMANDS = [
                                 break;
                             case 'application/pdf':
                                 const pdf = document.createElement('embed');
-                                pdf.src = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                                pdf.src = fileURL(f);
                                 pdf.type = f.mimeType;
                                 pdf.style = 'width: 100%; background-color: white';
                                 this.term.container.appendChild(pdf);
@@ -203,6 +219,43 @@ const COMMANDS = [
             return success ? Promise.resolve() : Promise.reject();
         }
     },
+    {
+        name: CMDNAMES.UP,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        description: tr('nach oben gehen'),
+        fn: async function() {
+            const success = await this.stepThroughDoorway('u');
+            return success ? Promise.resolve() : Promise.reject();
+        }
+    },
+    {
+        name: CMDNAMES.DOWN,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        description: tr('nach unten gehen'),
+        fn: async function() {
+            const success = await this.stepThroughDoorway('d');
+            return success ? Promise.resolve() : Promise.reject();
+        }
+    },
+    {
+        name: CMDNAMES.GO,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'exit',
+                type: 'string',
+            },
+        ],
+        description: tr('durch einen benannten Ausgang gehen, z.B. `go portal`'),
+        fn: async function(params) {
+            let [exit] = params;
+            while (typeof exit === 'undefined') {
+                exit = await this.getInput(tr('Ausgang: '));
+            }
+            const success = await this.stepThroughDoorway(exit);
+            return success ? Promise.resolve() : Promise.reject();
+        }
+    },
     {
         name: CMDNAMES.WHOAMI,
         roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
@@ -264,8 +317,8 @@ const COMMANDS = [
                     this.print(tr(`Dies ist der Eingang zum Labyrinth.`));
                 }
                 const directions = (this.user.in_room.neighbors.length > 1)
-                ? `${this.user.in_room.neighbors.slice(0, -1).map(neighbor => DIRECTIONS[neighbor.direction]).join(', ')} und ${DIRECTIONS[this.user.in_room.neighbors[this.user.in_room.neighbors.length-1].direction]}`
-                : DIRECTIONS[this.user.in_room.neighbors[0].direction];
+                ? `${this.user.in_room.neighbors.slice(0, -1).map(neighbor => directionName(neighbor.direction)).join(', ')} und ${directionName(this.user.in_room.neighbors[this.user.in_room.neighbors.length-1].direction)}`
+                : directionName(this.user.in_room.neighbors[0].direction);
                 const rand = Math.random();
                 const msg = rand > 0.7
                 ? tr(`Du siehst ${this.user.in_room.neighbors.length === 1 ? 'einen Durchgang' : 'Durchgänge'} nach ${directions}.`)
@@ -276,18 +329,20 @@ const COMMANDS = [
                         : tr(`In Richtung ${directions} kannst du weitergehen.`);
                 this.print(msg);
                 for (const neighbor of this.user.in_room.neighbors) {
-                    const solved = this.user.solved.find(rid => rid.riddle_id.$oid === neighbor.riddle_id.$oid);
+                    const solved = !neighbor.riddle_id || this.user.solved.find(rid => rid.riddle_id.$oid === neighbor.riddle_id.$oid);
                     const dirEl = document.createElement('span');
                     dirEl.className = 'clickable-direction';
-                    dirEl.textContent = DIRECTIONS[neighbor.direction];
+                    dirEl.textContent = directionName(neighbor.direction);
                     const boundClickHandler = function() {
                         const DIR_CMD_MAP = {
                             n: CMDNAMES.NORTH,
                             e: CMDNAMES.EAST,
                             s: CMDNAMES.SOUTH,
                             w: CMDNAMES.WEST,
+                            u: CMDNAMES.UP,
+                            d: CMDNAMES.DOWN,
                         };
-                        const cmd = DIR_CMD_MAP[neighbor.direction];
+                        const cmd = DIR_CMD_MAP[neighbor.direction] || `${CMDNAMES.GO} ${neighbor.direction}`;
                         this.term.enter(cmd);
                         dirEl.removeEventListener('click', boundClickHandler);
                     }.bind(this);
@@ -306,6 +361,403 @@ const COMMANDS = [
             }
         }
     },
+    {
+        name: CMDNAMES.LOOK,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        description: tr('dich im Raum umsehen'),
+        fn: async function() {
+            const reply = await authenticatedRequest(Game.URL.ROOM.LOOK)
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Du kannst nichts erkennen: ${reply.message}.`));
+                return Promise.reject();
+            }
+            if (reply.title) {
+                this.print(`<b>${reply.title}</b>`);
+            }
+            if (reply.asciiArt) {
+                const pre = document.createElement('pre');
+                pre.textContent = reply.asciiArt;
+                this.term.appendNode(pre);
+            }
+            if (reply.description) {
+                this.print(parseMarkdown(reply.description));
+            }
+            if (reply.objects.length > 0) {
+                this.print(tr(`Hier gibt es: ${reply.objects.join(', ')}.`));
+            }
+            if (reply.items.length > 0) {
+                this.print(tr(`Hier liegt: ${reply.items.join(', ')}.`));
+            }
+            for (const note of reply.notes) {
+                this.print(tr(`An der Wand steht: „${note.text.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}“ – <i>${note.username.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}</i> <small>(${note.id.$oid})</small>`));
+            }
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.EXAMINE,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'object',
+                type: 'string',
+            },
+        ],
+        description: tr('einen Gegenstand im Raum genauer untersuchen'),
+        fn: async function(params) {
+            let [object] = params;
+            while (typeof object === 'undefined') {
+                object = await this.getInput(tr('Was? '));
+            }
+            const reply = await authenticatedRequest(constructURL(Game.URL.ROOM.EXAMINE, {object: encodeURIComponent(object)}))
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Du findest hier kein(e) ${object}.`));
+                return Promise.reject();
+            }
+            this.print(parseMarkdown(reply.description || tr(`Nichts Besonderes an ${reply.name}.`)));
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.TAKE,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'item',
+                type: 'string',
+            },
+        ],
+        description: tr('einen Gegenstand aufheben und mitnehmen'),
+        fn: async function(params) {
+            let [item] = params;
+            while (typeof item === 'undefined') {
+                item = await this.getInput(tr('Was? '));
+            }
+            const reply = await authenticatedRequest(constructURL(Game.URL.ITEM.TAKE, {item: encodeURIComponent(item)}), 'POST')
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Hier liegt kein(e) ${item}.`));
+                return Promise.reject();
+            }
+            this.print(tr(`Du hast ${item} eingesteckt.`));
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.DROP,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'item',
+                type: 'string',
+            },
+        ],
+        description: tr('einen Gegenstand ablegen'),
+        fn: async function(params) {
+            let [item] = params;
+            while (typeof item === 'undefined') {
+                item = await this.getInput(tr('Was? '));
+            }
+            const reply = await authenticatedRequest(constructURL(Game.URL.ITEM.DROP, {item: encodeURIComponent(item)}), 'POST')
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Du hast kein(e) ${item} dabei.`));
+                return Promise.reject();
+            }
+            this.print(tr(`Du hast ${item} abgelegt.`));
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.INVENTORY,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        description: tr('anzeigen, was du bei dir trägst'),
+        fn: async function() {
+            const reply = await authenticatedRequest(Game.URL.INVENTORY)
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Das Inventar konnte nicht abgerufen werden: ${reply.message}.`));
+                return Promise.reject();
+            }
+            if (reply.items.length === 0) {
+                this.print(tr('Du trägst nichts bei dir.'));
+            }
+            else {
+                this.print(tr(`Du trägst bei dir: ${reply.items.join(', ')}.`));
+            }
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.MAP,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        description: tr('Karte der bisher besuchten Räume anzeigen'),
+        fn: async function() {
+            const reply = await authenticatedRequest(constructURL(Game.URL.GAME.MAP, {gameid: this.user.in_room.game_id.$oid}))
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Die Karte konnte nicht abgerufen werden: ${reply.message}.`));
+                return Promise.reject();
+            }
+            if (reply.ascii.length === 0) {
+                this.print(tr('Deine Karte ist noch leer.'));
+                return Promise.resolve();
+            }
+            const pre = document.createElement('pre');
+            pre.textContent = reply.ascii;
+            this.term.appendNode(pre);
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.TRAVEL,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'room_id',
+                type: 'string',
+            },
+        ],
+        description: tr('auf bekanntem Weg in einen schon besuchten Raum reisen'),
+        fn: async function(params) {
+            let [roomId] = params;
+            while (typeof roomId === 'undefined') {
+                roomId = await this.getInput(tr('Raum-ID: '));
+            }
+            const reply = await authenticatedRequest(constructURL(Game.URL.TRAVEL, {roomid: roomId}), 'POST')
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Dorthin kennst du keinen Weg: ${reply.message}.`));
+                return Promise.reject();
+            }
+            for (const step of reply.route) {
+                this.print(tr(`Du gehst nach ${directionName(step.direction)} in Raum ${step.coords || step.number}.`));
+            }
+            this.user.in_room = reply.room;
+            this.term.prompt = this.prompt;
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.ROUTE,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        description: tr('den Weg zurück zum Eingang anzeigen'),
+        fn: async function() {
+            const reply = await authenticatedRequest(Game.URL.ROUTE.ENTRANCE)
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Du findest den Weg zum Eingang nicht: ${reply.message}.`));
+                return Promise.reject();
+            }
+            if (reply.route.length === 0) {
+                this.print(tr('Du stehst bereits im Eingang.'));
+            }
+            else {
+                this.print(tr(`Der Weg zum Eingang: ${reply.route.map(step => directionName(step.direction)).join(', ')}.`));
+            }
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.SAY,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'text',
+                type: 'string',
+            },
+        ],
+        description: tr('etwas zu den anderen im Raum sagen'),
+        fn: async function(params) {
+            let text = params.join(' ');
+            while (text.length === 0) {
+                text = await this.getInput(tr('Was? '));
+            }
+            if (!this.live || this.live.readyState !== WebSocket.OPEN) {
+                this.print(tr('Niemand kann dich hören.'));
+                return Promise.reject();
+            }
+            this.live.send(JSON.stringify({type: 'Chat', text}));
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.TEAM,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'action',
+                type: 'optional_string',
+            },
+            {
+                name: 'param',
+                type: 'optional_string',
+            },
+        ],
+        description: tr('Team anzeigen, gründen (create), Leute einladen (invite), beitreten (join), verlassen (leave) oder Einladungen (invitations) zeigen'),
+        fn: async function(params) {
+            let [action, param] = params;
+            let reply;
+            switch (action) {
+                case 'create':
+                    reply = await authenticatedRequest(constructURL(Game.URL.TEAM.CREATE, {name: encodeURIComponent(param || await this.getInput(tr('Name des Teams: ')))}), 'POST')
+                        .then(response => response.json());
+                    break;
+                case 'invite':
+                    reply = await authenticatedRequest(constructURL(Game.URL.TEAM.INVITE, {username: encodeURIComponent(param || await this.getInput(tr('Wen? ')))}), 'POST')
+                        .then(response => response.json());
+                    break;
+                case 'join':
+                    reply = await authenticatedRequest(constructURL(Game.URL.TEAM.JOIN, {teamid: param || await this.getInput(tr('Team-ID: '))}), 'POST')
+                        .then(response => response.json());
+                    break;
+                case 'leave':
+                    reply = await authenticatedRequest(Game.URL.TEAM.LEAVE, 'POST')
+                        .then(response => response.json());
+                    if (reply.ok) {
+                        this.print(tr('Du hast dein Team verlassen.'));
+                        return Promise.resolve();
+                    }
+                    break;
+                case 'invitations':
+                    reply = await authenticatedRequest(Game.URL.TEAM.INVITATIONS)
+                        .then(response => response.json());
+                    if (reply.ok) {
+                        if (reply.teams.length === 0) {
+                            this.print(tr('Du hast keine Einladungen.'));
+                        }
+                        for (const team of reply.teams) {
+                            this.print(tr(`<b>${team.name.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}</b> (ID: ${team.id.$oid}) mit ${team.members.join(', ')}`));
+                        }
+                        return Promise.resolve();
+                    }
+                    break;
+                default:
+                    reply = await authenticatedRequest(Game.URL.TEAM.INFO)
+                        .then(response => response.json());
+                    break;
+            }
+            if (!reply.ok) {
+                this.print(tr(`Das hat nicht geklappt: ${reply.message}.`));
+                return Promise.reject();
+            }
+            this.print(tr(`<b>Team</b>        ${reply.name.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')} (ID: ${reply.id.$oid})`));
+            this.print(tr(`<b>Mitglieder</b>  ${reply.members.join(', ')}`));
+            if (reply.invitations.length > 0) {
+                this.print(tr(`<b>Eingeladen</b>  ${reply.invitations.join(', ')}`));
+            }
+            this.print(tr(`<b>Punkte</b>      ${reply.score}`));
+            this.print(tr(`<b>Level</b>       ${reply.level}`));
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.NOTE,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'text',
+                type: 'string',
+            },
+        ],
+        description: tr('eine Notiz für andere an die Wand schreiben'),
+        fn: async function(params) {
+            let text = params.join(' ');
+            while (text.length === 0) {
+                text = await this.getInput(tr('Was? '));
+            }
+            const reply = await authenticatedRequest(Game.URL.ROOM.NOTE, 'POST', {text})
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Du kannst hier nichts an die Wand schreiben: ${reply.message}.`));
+                return Promise.reject();
+            }
+            if (reply.hidden) {
+                this.print(tr('Deine Notiz verrät eine Lösung und wird deshalb nicht angezeigt.'));
+            }
+            else {
+                this.print(tr('Du hast eine Notiz an die Wand geschrieben.'));
+            }
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.REPORT,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'note_id',
+                type: 'string',
+            },
+        ],
+        description: tr('eine unangemessene Notiz melden'),
+        fn: async function(params) {
+            let [noteId] = params;
+            while (typeof noteId === 'undefined') {
+                noteId = await this.getInput(tr('Notiz-ID: '));
+            }
+            const reply = await authenticatedRequest(constructURL(Game.URL.NOTE.REPORT, {noteid: noteId}), 'POST')
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Die Meldung hat nicht geklappt: ${reply.message}.`));
+                return Promise.reject();
+            }
+            this.print(tr('Danke, die Notiz wurde gemeldet.'));
+            return Promise.resolve();
+        }
+    },
+    {
+        name: CMDNAMES.RATE,
+        roles: [ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
+        args: [
+            {
+                name: 'riddle_id',
+                type: 'string',
+            },
+            {
+                name: 'difficulty',
+                type: 'string',
+            },
+            {
+                name: 'fun',
+                type: 'string',
+            },
+            {
+                name: 'comment',
+                type: 'optional_string',
+            },
+        ],
+        description: tr('ein gelöstes Rätsel bewerten (Schwierigkeit und Spaß von 1 bis 5)'),
+        fn: async function(params) {
+            let [oid, difficulty, fun, ...comment] = params;
+            while (typeof oid === 'undefined') {
+                oid = await this.getInput(tr('Rätsel-ID: '));
+            }
+            while (typeof difficulty === 'undefined') {
+                difficulty = await this.getInput(tr('Wie schwer war es (1-5)? '));
+            }
+            while (typeof fun === 'undefined') {
+                fun = await this.getInput(tr('Wie viel Spaß hat es gemacht (1-5)? '));
+            }
+            const data = {
+                difficulty: difficulty | 0,
+                fun: fun | 0,
+            };
+            if (comment.length > 0) {
+                data.comment = comment.join(' ');
+            }
+            const reply = await authenticatedRequest(constructURL(Riddle.URL.FEEDBACK, {oid}), 'POST', data)
+                .then(response => response.json());
+            if (!reply.ok) {
+                this.print(tr(`Die Bewertung hat nicht geklappt: ${reply.message}.`));
+                return Promise.reject();
+            }
+            this.print(tr('Danke für deine Bewertung!'));
+            return Promise.resolve();
+        }
+    },
     {
         name: CMDNAMES.CLEAR,
         roles: [ROLE.ANON, ROLE.USER, ROLE.ADMIN, ROLE.DESIGNER],
@@ -35,4 +35,6 @@ const DIRECTIONS = {
     e: tr('Osten'),
     s: tr('Süden'),
     w: tr('Westen'),
+    u: tr('oben'),
+    d: tr('unten'),
 };
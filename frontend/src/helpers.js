@@ -5,6 +5,12 @@
     return (Date.now() / 1000) | 0;
 }
 
+/**
+ * @param {String} direction - a compass direction like 'n', or the name of an exit like 'portal'
+ * @returns {String} - the human readable name of the direction
+ */
+const directionName = direction => DIRECTIONS[direction] || direction;
+
 /**
  * @param {String} template - the URL template, e.g. "/user/get/by/:userid"
  * @param {object} params - an object: the values of the properties replace the placeholders in the URL with the same name as the properties'
@@ -19,6 +25,17 @@ const constructURL = (template, params) => {
     return url;
 };
 
+/**
+ * Get the URL of a file. Files generated by riddle scripts come with their
+ * data, all others have been uploaded.
+ * 
+ * @param file {object} - object containing information about the file
+ * @returns {string} the URL
+ */
+const fileURL = (file) => file.data
+    ? `data:${file.mimeType};base64,${file.data}`
+    : `${UPLOAD_FOLDER}/${file.uploadedName}`;
+
 /**
  * Construct a HTML element that, when clicked, will initiate a download of a file.
  * 
@@ -31,7 +48,7 @@ const makeDownloadLink = (file) => {
     let a = document.createElement('a');
     a.download = file.originalName;
     a.title = `Bild herunterladen als ${file.originalName}`;
-    a.href = `${UPLOAD_FOLDER}/${file.uploadedName}`;
+    a.href = fileURL(file);
     a.textContent = `Download ${file.originalName}`;
     span.appendChild(a);
     return span;
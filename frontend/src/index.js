@@ -27,11 +27,39 @@ class Game extends HTMLElement {
             },
         },
         GO: `${HOST}/go/:direction`,
+        TRAVEL: `${HOST}/travel/:roomid`,
+        NOTE: {
+            REPORT: `${HOST}/note/:noteid/report`,
+        },
+        ROUTE: {
+            ENTRANCE: `${HOST}/route/entrance`,
+        },
+        ROOM: {
+            LOOK: `${HOST}/room/look`,
+            EXAMINE: `${HOST}/room/examine/:object`,
+            NOTE: `${HOST}/room/note`,
+        },
+        ITEM: {
+            TAKE: `${HOST}/item/take/:item`,
+            DROP: `${HOST}/item/drop/:item`,
+        },
+        INVENTORY: `${HOST}/inventory`,
+        TEAM: {
+            INFO: `${HOST}/team`,
+            INVITATIONS: `${HOST}/team/invitations`,
+            CREATE: `${HOST}/team/create/:name`,
+            INVITE: `${HOST}/team/invite/:username`,
+            JOIN: `${HOST}/team/join/:teamid`,
+            LEAVE: `${HOST}/team/leave`,
+        },
         GAME: {
             STATS: `${HOST}/game/stats/:gameid`,
             HIGHSCORES: `${HOST}/game/highscores/:gameid`,
+            MAP: `${HOST}/game/:gameid/map`,
+            TEAMHIGHSCORES: `${HOST}/game/highscores/teams/:gameid`,
         },
         PING: `${HOST}/ping`,
+        LIVE: `${HOST.replace(/^http/, 'ws')}/live?token=:token`,
         CHEAT: `${HOST}/cheat`,
     };
     static COMMANDS = COMMANDS;
@@ -190,6 +218,7 @@ terminal-div {
                         this.user = user;
                         this.role = parsedJWT.role;
                         this.print(tr(`gültig. Du bist nun eingeloggt.`));
+                        this.connectLive();
                     }
                     else {
                         this.print(tr(`User nicht gefunden.`));
@@ -339,7 +368,7 @@ terminal-div {
         return data;
     }
     /**
-     * @param {String} direction - one of 'n', 'e', 's', 'w'
+     * @param {String} direction - one of 'n', 'e', 's', 'w', 'u', 'd', or the name of an exit
      * @returns {Boolean} - true if command succeeded, false otherwise
      */
     async stepThroughDoorway(direction) {
@@ -350,24 +379,30 @@ terminal-div {
         }
         const neighbor = this.user.in_room.neighbors.find(neighbor => neighbor.direction === direction);
         if (!neighbor) {
-            this.print(tr(`Du kannst nicht nach ${DIRECTIONS[direction]} gehen.`));
+            this.print(tr(`Du kannst nicht nach ${directionName(direction)} gehen.`));
             return false;
         }
-        const solved = this.user.solved.find(riddle => riddle.riddle_id.$oid === neighbor.riddle_id.$oid);
+        const solved = !neighbor.riddle_id || this.user.solved.find(riddle => riddle.riddle_id.$oid === neighbor.riddle_id.$oid);
         if (solved) {
             const reply = await this.go(direction);
             if (reply.ok) {
                 this.user.in_room = reply.room;
                 this.term.prompt = this.prompt;
                 if (this.user.in_room.exit) {
-                    this.print(tr(`Du bist nach ${DIRECTIONS[direction]} gegangen, direkt in die <strong>FREIHEIT</strong>! Gratulation! Du hast den Ausgang des Labyrinths gefunden.`));
+                    this.print(tr(`Du bist nach ${directionName(direction)} gegangen, direkt in die <strong>FREIHEIT</strong>! Gratulation! Du hast den Ausgang des Labyrinths gefunden.`));
                 }
                 else {
-                    this.print(tr(`Du bist nach ${DIRECTIONS[direction]} durch den Durchgang gegangen.`));
+                    this.print(tr(`Du bist nach ${directionName(direction)} durch den Durchgang gegangen.`));
                     this.execute(CMDNAMES.WHEREAMI);
                 }
                 return true;
             }
+            else if (neighbor.item && reply.message === 'item required') {
+                this.print(tr(`Die Tür lässt sich nur öffnen, wenn du ${neighbor.item} dabeihast.`));
+            }
+            else if (neighbor.condition) {
+                this.print(tr(`Die Tür lässt sich noch nicht öffnen. Vielleicht musst du erst woanders weiterkommen.`));
+            }
             else {
                 this.print(tr('Oh nein, irgendwas ist schiefgegangen. Diese Fehlermeldung hättest du eigentlich niemals sehen dürfen. Eigentlich ...'));
             }
@@ -393,7 +428,7 @@ terminal-div {
                             // fall-through
                         case 'image/webp':
                             const img = document.createElement('img');
-                            img.src = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                            img.src = fileURL(f);
                             if (f.variants) {
                                 img.srcset = f.variants.map(v => `${UPLOAD_FOLDER}/${v.uploadedName} ${v.scale}x`).join(' ');
                             }
@@ -405,7 +440,7 @@ terminal-div {
                             // fall-through
                         case 'image/svg+xml':
                             const svgimg = document.createElement('img');
-                            svgimg.src = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                            svgimg.src = fileURL(f);
                             this.term.container.appendChild(svgimg);
                             this.term.container.appendChild(makeDownloadLink(f));
                             break;
@@ -414,7 +449,9 @@ terminal-div {
                         case 'audio/webm':
                             // fall-through
                         case 'audio/flac':
-                            const audioUrl = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                            // fall-through
+                        case 'audio/wav':
+                            const audioUrl = fileURL(f);
                             this.print(`<audio autoplay controls><source src="${audioUrl}" type="${f.mimeType}"></audio>`);
                             break;
                         case 'text/plain':
@@ -431,9 +468,7 @@ terminal-div {
                             break;
                         case 'text/html':
                             const embed = document.createElement('embed');
-                            embed.src = f.data
-                                ? `data:text/html;base64,${f.data}`
-                                : `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                            embed.src = fileURL(f);
                             embed.type = f.mimeType;
                             embed.style.width = "512px";
                             embed.style.height = "512px";
@@ -449,7 +484,7 @@ terminal-div {
                             break;
                         case 'application/pdf':
                             const pdf = document.createElement('embed');
-                            pdf.src = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                            pdf.src = fileURL(f);
                             pdf.type = f.mimeType;
                             pdf.style = 'width: 100%; background-color: white';
                             this.term.container.appendChild(pdf);
@@ -460,7 +495,7 @@ terminal-div {
                         case 'video/mp4':
                             // fall-through
                         case 'video/ogg':
-                            const videoUrl = `${UPLOAD_FOLDER}/${f.uploadedName}`;
+                            const videoUrl = fileURL(f);
                             this.print(`<video width="512" autoplay controls><source src="${videoUrl}" type="${f.mimeType}"></video>`);
                             break;
                         default:
@@ -705,6 +740,44 @@ terminal-div {
     logout() {
         this.user = null;
         localStorage.removeItem('jwt');
+        if (this.live) {
+            this.live.close();
+            this.live = null;
+        }
+    }
+    /**
+     * Opens the WebSocket connection for presence events and the room chat.
+     */
+    connectLive() {
+        const jwt = localStorage.getItem('jwt');
+        this.live = new WebSocket(constructURL(Game.URL.LIVE, {token: encodeURIComponent(jwt)}));
+        this.live.addEventListener('message', event => {
+            const msg = JSON.parse(event.data);
+            switch (msg.type) {
+                case 'Presence':
+                    if (msg.usernames.length > 0) {
+                        this.print(tr(`Mit dir im Raum: ${msg.usernames.join(', ').replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}.`));
+                    }
+                    break;
+                case 'Entered':
+                    this.print(tr(`<i>${msg.username.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}</i> hat den Raum betreten.`));
+                    break;
+                case 'Left':
+                    this.print(tr(`<i>${msg.username.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}</i> hat den Raum verlassen.`));
+                    break;
+                case 'DoorSolved':
+                    this.print(tr(`<i>${msg.username.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}</i> hat eine Tür in deiner Nähe geöffnet.`));
+                    break;
+                case 'Chat':
+                    this.print(`<i>${msg.username.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}</i>: ${msg.text.replace(/&/g,'&amp;').replace(/</g,'&lt;').replace(/>/g,'&gt;')}`);
+                    break;
+                case 'Error':
+                    this.print(tr(`Chat: ${msg.message}.`));
+                    break;
+                default:
+                    break;
+            }
+        });
     }
     async whoami() {
         try {
@@ -4,6 +4,7 @@ class Riddle {
         LOAD: `${HOST}/riddle/:oid`,
         SOLVE: `${HOST}/riddle/solve/:oid`,
         DEBRIEFING: `${HOST}/riddle/debriefing/:oid`,
+        FEEDBACK: `${HOST}/riddle/:oid/feedback`,
     };
     static async loadByLevel(level) {
         const url = constructURL(Riddle.URL.INFO, {level});